@@ -6,6 +6,8 @@
     unused_extern_crates,
     rust_2021_compatibility
 )]
+// Bevy and its plugins depend on several versions of some crates.
+#![allow(clippy::multiple_crate_versions)]
 
 use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
@@ -59,6 +61,8 @@ fn setup(mut commands: Commands, mut windows: ResMut<Windows>) {
     window.set_cursor_grab_mode(bevy::window::CursorGrabMode::Locked);
     window.set_cursor_visibility(false);
 
+    // Sunlight for entities such as falling blocks and the player's body. Chunks are unlit
+    // and only shaded by the light baked into their meshes, so caves stay dark.
     commands.spawn(DirectionalLightBundle {
         directional_light: DirectionalLight {
             illuminance: 32000.0,
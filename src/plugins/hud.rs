@@ -8,15 +8,20 @@ pub struct HudPlugin;
 
 impl Plugin for HudPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
             .add_enter_system(crate::AppState::InGame, hud_setup)
-            .add_system(hud_system.run_in_state(crate::AppState::InGame));
+            .add_system(hud_system.run_in_state(crate::AppState::InGame))
+            .add_system(stamina_bar_system.run_in_state(crate::AppState::InGame));
     }
 }
 
 #[derive(Component)]
 struct FpsText;
 
+/// The filled part of the stamina bar.
+#[derive(Component)]
+struct StaminaBar;
+
 fn hud_setup(mut commands: Commands, res: Res<super::resources::GameResources>) {
     commands
         .spawn(
@@ -34,6 +39,32 @@ fn hud_setup(mut commands: Commands, res: Res<super::resources::GameResources>)
                     font_size: 20.0,
                     color: Color::GOLD,
                 }),
+                TextSection::new(
+                    "\nChunks: ",
+                    TextStyle {
+                        font: res.font.clone(),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                TextSection::from_style(TextStyle {
+                    font: res.font.clone(),
+                    font_size: 20.0,
+                    color: Color::GOLD,
+                }),
+                TextSection::new(
+                    "\nHealth: ",
+                    TextStyle {
+                        font: res.font.clone(),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                TextSection::from_style(TextStyle {
+                    font: res.font.clone(),
+                    font_size: 20.0,
+                    color: Color::GOLD,
+                }),
             ])
             .with_style(Style {
                 position_type: PositionType::Absolute,
@@ -60,14 +91,74 @@ fn hud_setup(mut commands: Commands, res: Res<super::resources::GameResources>)
             ..default()
         }),
     );
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(40.0),
+                    bottom: Val::Percent(4.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(20.0), Val::Px(8.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                        ..default()
+                    },
+                    background_color: Color::GOLD.into(),
+                    ..default()
+                },
+                StaminaBar,
+            ));
+        });
 }
 
-fn hud_system(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<FpsText>>) {
+fn hud_system(
+    diagnostics: Res<Diagnostics>,
+    mut query: Query<&mut Text, With<FpsText>>,
+    players: Query<&super::player::health::Health>,
+) {
     for mut text in &mut query {
         if let Some(fps) = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS) {
             if let Some(average) = fps.average() {
                 text.sections[1].value = format!("{}", average.ceil());
             }
         }
+        if let Some(bytes) = diagnostics
+            .get(super::terrain::cache::CHUNK_MEMORY)
+            .and_then(bevy::diagnostic::Diagnostic::value)
+        {
+            text.sections[3].value = format!("{:.1} MiB", bytes / (1024.0 * 1024.0));
+        }
+        if let Ok(health) = players.get_single() {
+            text.sections[5].value = format!("{:.0}/{:.0}", health.current.ceil(), health.max);
+        }
+    }
+}
+
+fn stamina_bar_system(
+    players: Query<&super::player::stamina::Stamina>,
+    settings: Res<super::player::stamina::StaminaSettings>,
+    mut bars: Query<&mut Style, With<StaminaBar>>,
+) {
+    let Ok(stamina) = players.get_single() else {
+        return;
+    };
+    let fraction = if settings.max > 0.0 {
+        (stamina.current / settings.max).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    for mut style in &mut bars {
+        style.size.width = Val::Percent(fraction * 100.0);
     }
 }
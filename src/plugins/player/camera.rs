@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use super::{PlayerController, PlayerInputMap, Rumble, PITCH_BOUND};
+
+/// Space kept between the third person camera and the wall it is pulled in front of.
+const WALL_MARGIN: f32 = 0.2;
+
+/// How the player's camera is placed, cycled through at runtime.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Looking out of the player's eyes.
+    #[default]
+    FirstPerson,
+    /// Orbiting behind the player, pulled in front of the walls in the way.
+    ThirdPerson,
+    /// Flying around on its own. The player stays where it stands and ignores the controls.
+    FreeFly,
+}
+
+impl CameraMode {
+    #[must_use]
+    pub const fn next(self) -> Self {
+        match self {
+            Self::FirstPerson => Self::ThirdPerson,
+            Self::ThirdPerson => Self::FreeFly,
+            Self::FreeFly => Self::FirstPerson,
+        }
+    }
+}
+
+/// The camera of the player. It always stays a child of the player entity, even when it
+/// flies on its own.
+#[derive(Clone, Component, Copy, Debug)]
+pub struct PlayerCamera {
+    pub mode: CameraMode,
+    /// Distance of the third person camera from the player's eyes, when nothing is in the way.
+    pub distance: f32,
+    /// Speed of the free camera in blocks per second, doubled while running.
+    pub free_speed: f32,
+    /// World position of the free camera.
+    free_translation: Vec3,
+    free_yaw_pitch: Vec2,
+}
+
+impl Default for PlayerCamera {
+    fn default() -> Self {
+        Self {
+            mode: CameraMode::FirstPerson,
+            distance: 4.0,
+            free_speed: 10.0,
+            free_translation: Vec3::ZERO,
+            free_yaw_pitch: Vec2::ZERO,
+        }
+    }
+}
+
+/// Placeholder model of the player's body, filling its collider. Only drawn when the camera
+/// is out of the player's eyes.
+#[derive(Clone, Component, Copy, Debug)]
+pub struct PlayerBody;
+
+/// Gives new players a body.
+pub fn body_system(
+    mut commands: Commands,
+    players: Query<Entity, Added<PlayerController>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut model: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+) {
+    for player in &players {
+        let (mesh, material) = model
+            .get_or_insert_with(|| {
+                (
+                    meshes.add(shape::Cube { size: 1.0 }.into()),
+                    materials.add(Color::rgb(0.8, 0.6, 0.4).into()),
+                )
+            })
+            .clone();
+        commands.entity(player).with_children(|parent| {
+            parent.spawn((
+                PbrBundle {
+                    mesh,
+                    material,
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                },
+                PlayerBody,
+            ));
+        });
+    }
+}
+
+/// The player's body, kept apart from the player and camera transforms.
+type BodyFilter = (
+    With<PlayerBody>,
+    Without<PlayerCamera>,
+    Without<PlayerController>,
+);
+
+/// Switches between camera modes on request, and places the camera for the current one.
+/// Runs once the player has moved for the frame, before transforms are propagated.
+pub fn camera_system(
+    players: Query<(
+        Entity,
+        &ActionState<PlayerInputMap>,
+        &PlayerController,
+        &Transform,
+    )>,
+    mut cameras: Query<(&mut Transform, &mut PlayerCamera, &Rumble), Without<PlayerController>>,
+    mut bodies: Query<(&mut Transform, &mut Visibility), BodyFilter>,
+    rapier_context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let (entity, action, controller, player) = players.single();
+    let (mut transform, mut camera, rumble) = cameras.single_mut();
+
+    if action.just_pressed(PlayerInputMap::CycleCamera) {
+        camera.mode = camera.mode.next();
+        if camera.mode == CameraMode::FreeFly {
+            // Take off from the player's eyes.
+            camera.free_translation = controller.eye(player).0;
+            camera.free_yaw_pitch = controller.yaw_pitch;
+        }
+    }
+
+    // The body follows the collider as the player crouches.
+    for (mut body, mut visibility) in &mut bodies {
+        body.scale = controller.half_extents() * 2.0;
+        visibility.is_visible = camera.mode != CameraMode::FirstPerson;
+    }
+
+    let pitch = Quat::from_rotation_x(controller.yaw_pitch.y);
+    match camera.mode {
+        CameraMode::FirstPerson => {
+            transform.translation = controller.camera_offset() + rumble.offset;
+            transform.rotation = pitch;
+        }
+        CameraMode::ThirdPerson => {
+            let (eye, forward) = controller.eye(player);
+            let filter = QueryFilter::new()
+                .exclude_rigid_body(entity)
+                .exclude_sensors();
+            let distance = rapier_context
+                .cast_ray(eye, -forward, camera.distance + WALL_MARGIN, true, filter)
+                .map_or(camera.distance, |(_, toi)| (toi - WALL_MARGIN).max(0.0));
+            transform.translation =
+                controller.camera_offset() + pitch * Vec3::Z * distance + rumble.offset;
+            transform.rotation = pitch;
+        }
+        CameraMode::FreeFly => {
+            let cursor_delta = -action
+                .axis_pair(PlayerInputMap::PanCamera)
+                .unwrap_or_default()
+                .xy();
+            camera.free_yaw_pitch += cursor_delta * controller.mouse_rotate_sensitivity;
+            camera.free_yaw_pitch.y = camera.free_yaw_pitch.y.clamp(-PITCH_BOUND, PITCH_BOUND);
+            let rotation = Quat::from_rotation_y(camera.free_yaw_pitch.x)
+                * Quat::from_rotation_x(camera.free_yaw_pitch.y);
+
+            // Moves along where the camera looks, and straight up and down.
+            let direction = [
+                (PlayerInputMap::MoveForward, rotation * -Vec3::Z),
+                (PlayerInputMap::MoveBackwards, rotation * Vec3::Z),
+                (PlayerInputMap::MoveRight, rotation * Vec3::X),
+                (PlayerInputMap::MoveLeft, rotation * -Vec3::X),
+                (PlayerInputMap::Jump, Vec3::Y),
+                (PlayerInputMap::Crouch, -Vec3::Y),
+            ]
+            .iter()
+            .filter(|(key, _)| action.pressed(*key))
+            .map(|(_, dir)| *dir)
+            .sum::<Vec3>()
+            .normalize_or_zero();
+            let speed = if action.pressed(PlayerInputMap::Run) {
+                camera.free_speed * 2.0
+            } else {
+                camera.free_speed
+            };
+            camera.free_translation += direction * speed * time.delta_seconds();
+
+            // Undo the player's placement, as the camera is still its child.
+            let inverse = player.rotation.inverse();
+            transform.translation = inverse * (camera.free_translation - player.translation);
+            transform.rotation = inverse * rotation;
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use super::{movement::MovementState, PlayerController, PlayerInputMap};
+
+/// Rules the player plays by, cycled through at runtime.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Resource)]
+pub enum GameMode {
+    /// Blocks take time to break and are used up when placed. Flying is not allowed.
+    #[default]
+    Survival,
+    /// Blocks break at once and never run out, and the player can fly.
+    Creative,
+    /// The player flies through the terrain without touching it.
+    Spectator,
+}
+
+impl GameMode {
+    #[must_use]
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Survival => Self::Creative,
+            Self::Creative => Self::Spectator,
+            Self::Spectator => Self::Survival,
+        }
+    }
+
+    /// Whether the player can take off and land at will. Spectators always fly.
+    #[must_use]
+    pub const fn can_fly(self) -> bool {
+        matches!(self, Self::Creative)
+    }
+
+    /// Whether blocks break at once and are never used up.
+    #[must_use]
+    pub const fn is_creative(self) -> bool {
+        matches!(self, Self::Creative)
+    }
+
+    /// Whether the player can be hurt.
+    #[must_use]
+    pub const fn takes_damage(self) -> bool {
+        matches!(self, Self::Survival)
+    }
+
+    /// Whether the player can break and place blocks.
+    #[must_use]
+    pub const fn can_interact(self) -> bool {
+        !matches!(self, Self::Spectator)
+    }
+}
+
+/// Switches to the next game mode on request, and applies changes to the player. Spectators
+/// lose their collider and always fly, survival players can't fly.
+pub fn game_mode_system(
+    mut commands: Commands,
+    mut mode: ResMut<GameMode>,
+    mut players: Query<(Entity, &ActionState<PlayerInputMap>, &mut PlayerController)>,
+) {
+    let (entity, action, mut controller) = players.single_mut();
+    if action.just_pressed(PlayerInputMap::CycleGameMode) {
+        *mode = mode.next();
+    }
+    if !mode.is_changed() {
+        return;
+    }
+
+    if *mode == GameMode::Spectator {
+        commands.entity(entity).remove::<Collider>();
+        controller.state = MovementState::Flying;
+        return;
+    }
+    let half_extents = controller.half_extents();
+    commands.entity(entity).insert(Collider::cuboid(
+        half_extents.x,
+        half_extents.y,
+        half_extents.z,
+    ));
+    if !mode.can_fly() && controller.state == MovementState::Flying {
+        controller.state = MovementState::Walking;
+    }
+}
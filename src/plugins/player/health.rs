@@ -0,0 +1,248 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    game_mode::GameMode,
+    movement::MovementState,
+    stamina::{Stamina, StaminaSettings},
+    PlayerController,
+};
+use crate::plugins::terrain::{block, gravity::FallingBlock, world::VoxelWorld};
+
+/// Landing speed, in blocks per second, up to which falls don't hurt. About a three block
+/// drop.
+const SAFE_LANDING_SPEED: f32 = 13.0;
+/// Damage per block per second of landing speed above [`SAFE_LANDING_SPEED`].
+const FALL_DAMAGE: f32 = 1.0;
+const LAVA_DAMAGE: f32 = 4.0;
+const SUFFOCATION_DAMAGE: f32 = 1.0;
+/// Damage dealt by a falling block landing on the player.
+const CRUSH_DAMAGE: f32 = 6.0;
+/// Speed given to an entity hit by another one, away from it.
+const KNOCKBACK: f32 = 6.0;
+
+#[derive(Clone, Component, Copy, Debug)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    /// Seconds after being hurt during which further damage is ignored.
+    pub invulnerability: f32,
+    invulnerable_for: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            current: 20.0,
+            max: 20.0,
+            invulnerability: 0.5,
+            // Spawning is protected as well.
+            invulnerable_for: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageSource {
+    /// Landing too fast.
+    Fall,
+    Lava,
+    /// Being stuck with the head inside a block.
+    Suffocation,
+    /// Hit by another entity, like a mob or a falling block. The target is knocked back away
+    /// from it.
+    Attack(Entity),
+}
+
+/// Sent to hurt an entity with [`Health`].
+#[derive(Clone, Copy, Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub source: DamageSource,
+    pub amount: f32,
+}
+
+/// Hurts the player when it lands too fast, stands in lava, has its head stuck in a block or
+/// is hit by a falling block.
+pub fn hazard_system(
+    players: Query<(
+        Entity,
+        &PlayerController,
+        &Transform,
+        Option<&KinematicCharacterControllerOutput>,
+    )>,
+    falling_blocks: Query<(Entity, &Transform, &Velocity), With<FallingBlock>>,
+    world: Res<VoxelWorld>,
+    mut damage: EventWriter<DamageEvent>,
+    mut fall_speed: Local<f32>,
+) {
+    let (player, controller, transform, output) = players.single();
+    let mut hurt = |source, amount| {
+        damage.send(DamageEvent {
+            target: player,
+            source,
+            amount,
+        });
+    };
+
+    // The speed of the last move before touching the ground is the landing speed. Runs
+    // after the controller, so its velocity is the one of the move about to be made. Fluids,
+    // ladders and flying break the fall.
+    match output {
+        Some(output) if controller.state == MovementState::Walking => {
+            if output.grounded {
+                if *fall_speed > SAFE_LANDING_SPEED {
+                    hurt(
+                        DamageSource::Fall,
+                        (*fall_speed - SAFE_LANDING_SPEED) * FALL_DAMAGE,
+                    );
+                }
+                *fall_speed = 0.0;
+            } else {
+                *fall_speed = -controller.velocity.y;
+            }
+        }
+        _ => *fall_speed = 0.0,
+    }
+
+    let voxel = |pos: Vec3| world.get(pos.round().as_ivec3());
+    let half_extents = controller.half_extents();
+    let feet = transform.translation - Vec3::Y * (half_extents.y - 0.1);
+    let eyes = transform.translation + controller.camera_offset();
+    if [feet, transform.translation, eyes]
+        .iter()
+        .any(|&pos| voxel(pos) == Some(block::LAVA))
+    {
+        hurt(DamageSource::Lava, LAVA_DAMAGE);
+    }
+    if voxel(eyes).is_some_and(|voxel| {
+        let def = block::def(voxel);
+        def.solid && def.shape.is_full()
+    }) {
+        hurt(DamageSource::Suffocation, SUFFOCATION_DAMAGE);
+    }
+
+    for (entity, block, velocity) in &falling_blocks {
+        let offset = (block.translation - transform.translation).abs();
+        if velocity.linvel.y < -1.0 && offset.cmplt(half_extents + 0.5).all() {
+            hurt(DamageSource::Attack(entity), CRUSH_DAMAGE);
+        }
+    }
+}
+
+type TargetQuery<'a> = (
+    &'a mut Health,
+    &'a mut Transform,
+    &'a GlobalTransform,
+    Option<&'a mut PlayerController>,
+    Option<&'a mut Stamina>,
+);
+
+/// Applies damage, knocks the player back from its attackers, and respawns it at the spawn
+/// point when it dies.
+/// Only survival players can be hurt.
+pub fn damage_system(
+    mut events: EventReader<DamageEvent>,
+    mut targets: Query<TargetQuery>,
+    attackers: Query<&GlobalTransform>,
+    world: Res<VoxelWorld>,
+    stamina_settings: Res<StaminaSettings>,
+    mode: Res<GameMode>,
+    time: Res<Time>,
+) {
+    for (mut health, ..) in &mut targets {
+        health.invulnerable_for = (health.invulnerable_for - time.delta_seconds()).max(0.0);
+    }
+
+    for event in events.iter() {
+        let Ok((mut health, mut transform, global_transform, controller, stamina)) =
+            targets.get_mut(event.target)
+        else {
+            continue;
+        };
+        if !mode.takes_damage() || health.invulnerable_for > 0.0 || health.current <= 0.0 {
+            continue;
+        }
+        health.current = (health.current - event.amount).max(0.0);
+        health.invulnerable_for = health.invulnerability;
+        let Some(mut controller) = controller else {
+            continue;
+        };
+
+        if let DamageSource::Attack(attacker) = event.source {
+            if let Ok(attacker) = attackers.get(attacker) {
+                let away = (global_transform.translation() - attacker.translation())
+                    * Vec3::new(1.0, 0.0, 1.0);
+                controller.velocity += (away.normalize_or_zero() + Vec3::Y * 0.5) * KNOCKBACK;
+            }
+        }
+
+        if health.current <= 0.0 {
+            // Start over at the spawn point, keeping the entity and everything else about
+            // the player, like its inventory and settings.
+            health.current = health.max;
+            if let Some(mut stamina) = stamina {
+                *stamina = Stamina::new(&stamina_settings);
+            }
+            controller.velocity = Vec3::ZERO;
+            controller.state = MovementState::Walking;
+            transform.translation = super::spawn_point(&world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dying_respawns_the_same_player() {
+        let mut app = App::new();
+        app.add_event::<DamageEvent>()
+            .init_resource::<VoxelWorld>()
+            .init_resource::<StaminaSettings>()
+            .init_resource::<GameMode>()
+            .init_resource::<Time>()
+            .add_system(damage_system);
+        let settings = StaminaSettings::default();
+        let mut stamina = Stamina::new(&settings);
+        stamina.current = 0.0;
+        let player = app
+            .world
+            .spawn((
+                Health {
+                    current: 3.0,
+                    invulnerable_for: 0.0,
+                    ..default()
+                },
+                Transform::from_xyz(40.0, 12.0, -7.0),
+                GlobalTransform::default(),
+                PlayerController {
+                    velocity: Vec3::new(2.0, -30.0, 0.0),
+                    state: MovementState::Swimming,
+                    ..default()
+                },
+                stamina,
+            ))
+            .id();
+
+        app.world.send_event(DamageEvent {
+            target: player,
+            source: DamageSource::Fall,
+            amount: 5.0,
+        });
+        app.update();
+
+        let player = app.world.entity(player);
+        let health = player.get::<Health>().unwrap();
+        assert_eq!(health.current, health.max);
+        assert_eq!(player.get::<Stamina>().unwrap().current, settings.max);
+        let controller = player.get::<PlayerController>().unwrap();
+        assert_eq!(controller.velocity, Vec3::ZERO);
+        assert_eq!(controller.state, MovementState::Walking);
+        assert_eq!(
+            player.get::<Transform>().unwrap().translation,
+            super::super::spawn_point(&VoxelWorld::default())
+        );
+    }
+}
@@ -0,0 +1,820 @@
+use bevy::{prelude::*, utils::HashMap, window::CursorGrabMode};
+use bevy_atmosphere::prelude::*;
+use bevy_rapier3d::prelude::*;
+use iyes_loopless::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use super::terrain::{
+    block,
+    fluid::Fluid,
+    spawn,
+    state::{BlockState, StateKind},
+    support::CaveInEvent,
+    world::{EditKind, VoxelEditEvent, VoxelID, VoxelWorld},
+};
+use camera::{CameraMode, PlayerCamera};
+use game_mode::GameMode;
+use health::{DamageEvent, Health};
+use movement::{Heading, MovementState, Surroundings};
+use stamina::{Stamina, StaminaSettings};
+
+mod camera;
+mod game_mode;
+pub mod health;
+mod movement;
+pub mod stamina;
+
+pub struct PlayerPlugin;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum PlayerSystem {
+    /// Turns and moves the player from its input.
+    Control,
+}
+
+#[derive(Actionlike, Clone, Copy)]
+pub enum PlayerInputMap {
+    PanCamera,
+    MoveForward,
+    MoveBackwards,
+    MoveLeft,
+    MoveRight,
+    Run,
+    Jump,
+    Crouch,
+    Break,
+    Place,
+    Excavate,
+    Detonate,
+    ToggleFly,
+    CycleGameMode,
+    CycleCamera,
+}
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(AtmospherePlugin)
+            .insert_resource(AtmosphereSettings {
+                resolution: 2048,
+                ..default()
+            })
+            .add_plugin(InputManagerPlugin::<PlayerInputMap>::default())
+            .init_resource::<GameMode>()
+            .add_asset::<StaminaSettings>()
+            .init_asset_loader::<stamina::StaminaSettingsLoader>()
+            .add_event::<DamageEvent>()
+            .add_enter_system(
+                crate::AppState::InGame,
+                player_setup.after(super::terrain::TerrainSystem::Setup),
+            )
+            .add_system(game_mode::game_mode_system.run_in_state(crate::AppState::InGame))
+            .add_system(
+                control_system
+                    .run_in_state(crate::AppState::InGame)
+                    .label(PlayerSystem::Control),
+            )
+            .add_system(interaction_system.run_in_state(crate::AppState::InGame))
+            .add_system(rumble_system.run_in_state(crate::AppState::InGame))
+            .add_system(camera::body_system.run_in_state(crate::AppState::InGame))
+            // After the character controller's move is written back.
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                camera::camera_system
+                    .run_in_state(crate::AppState::InGame)
+                    .before(bevy::transform::TransformSystem::TransformPropagate),
+            )
+            .add_system(
+                health::hazard_system
+                    .run_in_state(crate::AppState::InGame)
+                    .after(PlayerSystem::Control),
+            )
+            .add_system(health::damage_system.run_in_state(crate::AppState::InGame))
+            .add_system(stamina::stamina_system.run_in_state(crate::AppState::InGame));
+    }
+}
+
+fn player_setup(
+    mut commands: Commands,
+    world: Res<VoxelWorld>,
+    res: Res<super::resources::GameResources>,
+    stamina_settings: Res<Assets<StaminaSettings>>,
+) {
+    // Settings that failed to load were already reported by the asset server.
+    let stamina_settings = stamina_settings
+        .get(&res.stamina_settings)
+        .copied()
+        .unwrap_or_default();
+    commands.insert_resource(stamina_settings);
+    spawn_player(&mut commands, &world, &stamina_settings);
+}
+
+/// Where the player spawns: on the world's spawn point, or the safe place nearest to it if
+/// it was built over since. Without either, on the surface above it.
+fn spawn_point(world: &VoxelWorld) -> Vec3 {
+    let spawn = world
+        .spawn
+        .unwrap_or_else(|| spawn::surface(world, spawn::WORLD_SPAWN));
+    let column = IVec2::new(spawn.x, spawn.z);
+    let pos = if spawn::is_safe(world, spawn) {
+        spawn
+    } else {
+        spawn::find(world, column, spawn::SEARCH_RADIUS)
+            .unwrap_or_else(|| spawn::surface(world, column))
+    };
+    // Stand on the bottom of the free block, a little above the floor.
+    pos.as_vec3() + Vec3::Y * (PLAYER_HALF_EXTENTS.y - 0.5 + 0.01)
+}
+
+/// Spawns the player with its camera at the spawn point, as it is at the start of the game.
+fn spawn_player(commands: &mut Commands, world: &VoxelWorld, stamina_settings: &StaminaSettings) {
+    let mut input_map = InputMap::new([
+        (KeyCode::W, PlayerInputMap::MoveForward),
+        (KeyCode::S, PlayerInputMap::MoveBackwards),
+        (KeyCode::A, PlayerInputMap::MoveLeft),
+        (KeyCode::D, PlayerInputMap::MoveRight),
+        (KeyCode::LShift, PlayerInputMap::Run),
+        (KeyCode::RShift, PlayerInputMap::Run),
+        (KeyCode::Space, PlayerInputMap::Jump),
+        (KeyCode::LControl, PlayerInputMap::Crouch),
+        (KeyCode::RControl, PlayerInputMap::Crouch),
+        (KeyCode::X, PlayerInputMap::Detonate),
+        (KeyCode::F, PlayerInputMap::ToggleFly),
+        (KeyCode::F4, PlayerInputMap::CycleGameMode),
+        (KeyCode::F5, PlayerInputMap::CycleCamera),
+    ]);
+    input_map.insert(DualAxis::mouse_motion(), PlayerInputMap::PanCamera);
+    input_map.insert(MouseButton::Left, PlayerInputMap::Break);
+    input_map.insert(MouseButton::Right, PlayerInputMap::Place);
+    input_map.insert(MouseButton::Middle, PlayerInputMap::Excavate);
+    commands
+        .spawn((
+            TransformBundle::from(Transform::from_translation(spawn_point(world))),
+            RigidBody::KinematicPositionBased,
+            Collider::cuboid(
+                PLAYER_HALF_EXTENTS.x,
+                PLAYER_HALF_EXTENTS.y,
+                PLAYER_HALF_EXTENTS.z,
+            ),
+            KinematicCharacterController {
+                // Slide along walls instead of stopping dead.
+                slide: true,
+                ..default()
+            },
+            PlayerController::default(),
+            Inventory::default(),
+            Health::default(),
+            Stamina::new(stamina_settings),
+            InputManagerBundle::<PlayerInputMap> {
+                action_state: ActionState::default(),
+                input_map,
+            },
+            VisibilityBundle::default(),
+        ))
+        .with_children(|v| {
+            v.spawn((
+                Camera3dBundle {
+                    transform: Transform::from_translation(CAMERA_OFFSET),
+                    ..default()
+                },
+                AtmosphereCamera::default(),
+                PlayerCamera::default(),
+                Rumble::default(),
+            ));
+        });
+}
+
+#[derive(Clone, Component, Copy, Debug)]
+pub struct PlayerController {
+    pub mouse_rotate_sensitivity: Vec2,
+    /// Yaw and pitch the camera is turned to, trailing the mouse while smoothed.
+    pub yaw_pitch: Vec2,
+    /// Time constant of the mouse-look smoothing, in seconds: the camera covers about two
+    /// thirds of the remaining turn in that time. `None` follows the mouse without smoothing.
+    pub camera_smoothing: Option<f32>,
+    pub state: MovementState,
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    /// How quickly the player reaches the speed it is steered to, in blocks per second squared.
+    pub acceleration: f32,
+    pub gravity: f32,
+    /// Vertical speed given by a jump.
+    pub jump_speed: f32,
+    /// Fraction of the usual acceleration available while airborne.
+    pub air_control: f32,
+    /// Seconds after walking off a ledge during which a jump is still allowed.
+    pub coyote_time: f32,
+    /// Steepest slope the player can walk up, in radians.
+    pub max_slope: f32,
+    /// Highest ledge the player steps onto without jumping.
+    pub step_height: f32,
+    /// How far down the player sticks to the ground when walking down steps and slopes.
+    pub snap_distance: f32,
+    pub crouch_speed: f32,
+    pub swim_speed: f32,
+    /// Upward acceleration given by a stroke while swimming, in blocks per second squared.
+    pub swim_stroke: f32,
+    /// Upward push of a fully submerged body, relative to gravity. Below one it slowly sinks.
+    pub buoyancy: f32,
+    /// How quickly vertical speed is lost in a fluid, per second.
+    pub water_drag: f32,
+    pub climb_speed: f32,
+    /// How much shorter the player is while crouching.
+    pub crouch_depth: f32,
+    /// Yaw and pitch the mouse points to.
+    look_target: Vec2,
+    velocity: Vec3,
+    time_since_grounded: f32,
+    /// Whether the player ran during the last frame.
+    sprinting: bool,
+    /// Whether the player jumped during the last frame.
+    jumped: bool,
+    /// Whether the player has the stamina to run.
+    can_sprint: bool,
+    crouching: bool,
+}
+
+impl Default for PlayerController {
+    fn default() -> Self {
+        Self {
+            mouse_rotate_sensitivity: Vec2::splat(0.01),
+            yaw_pitch: Vec2::ZERO,
+            camera_smoothing: Some(0.075),
+            look_target: Vec2::ZERO,
+            state: MovementState::Walking,
+            walk_speed: 8.0,
+            run_speed: 12.0,
+            acceleration: 60.0,
+            gravity: 25.0,
+            jump_speed: 8.5,
+            air_control: 0.1,
+            coyote_time: 0.1,
+            max_slope: 45_f32.to_radians(),
+            step_height: 1.05,
+            snap_distance: 0.5,
+            crouch_speed: 2.5,
+            swim_speed: 4.0,
+            swim_stroke: 20.0,
+            buoyancy: 0.9,
+            water_drag: 2.0,
+            climb_speed: 3.0,
+            crouch_depth: 0.5,
+            velocity: Vec3::ZERO,
+            time_since_grounded: 0.0,
+            sprinting: false,
+            jumped: false,
+            can_sprint: true,
+            crouching: false,
+        }
+    }
+}
+
+impl PlayerController {
+    /// Half extents of the player's collider in its current stance.
+    fn half_extents(&self) -> Vec3 {
+        if self.crouching {
+            PLAYER_HALF_EXTENTS - Vec3::Y * self.crouch_depth / 2.0
+        } else {
+            PLAYER_HALF_EXTENTS
+        }
+    }
+
+    /// Where the player looks from and towards in world space, given the transform of its
+    /// body.
+    fn eye(&self, transform: &Transform) -> (Vec3, Vec3) {
+        let rotation = transform.rotation * Quat::from_rotation_x(self.yaw_pitch.y);
+        (
+            transform.translation + transform.rotation * self.camera_offset(),
+            rotation * -Vec3::Z,
+        )
+    }
+
+    /// Position of the camera relative to the player's body in its current stance. The body
+    /// shrinks around its center while crouching, so the camera only drops by half the depth
+    /// relative to it.
+    fn camera_offset(&self) -> Vec3 {
+        if self.crouching {
+            CAMERA_OFFSET - Vec3::Y * self.crouch_depth / 2.0
+        } else {
+            CAMERA_OFFSET
+        }
+    }
+
+    /// Turns the view towards where the mouse moved by `cursor_delta` pixels, smoothed over
+    /// the `dt` seconds of the frame.
+    fn look(&mut self, cursor_delta: Vec2, dt: f32) {
+        self.look_target += cursor_delta * self.mouse_rotate_sensitivity;
+        self.look_target.y = self.look_target.y.clamp(-PITCH_BOUND, PITCH_BOUND);
+        self.yaw_pitch = smooth(self.yaw_pitch, self.look_target, self.camera_smoothing, dt);
+    }
+}
+
+/// Position of the camera relative to the player's body.
+const CAMERA_OFFSET: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+/// Cave-ins and explosions further than this from the player, in blocks, can't be felt.
+const RUMBLE_DISTANCE: f32 = 48.0;
+/// How far away the player can reach blocks.
+const REACH: f32 = 8.0;
+/// Half extents of the player's collider.
+const PLAYER_HALF_EXTENTS: Vec3 = Vec3::new(0.5, 1.0, 0.5);
+const EXCAVATE_RADIUS: f32 = 2.5;
+const EXPLOSION_POWER: f32 = 4.0;
+/// Thickness of the boxes cast under the player to find the ground.
+const GROUND_PROBE: f32 = 0.05;
+/// How far below its feet the player still counts as standing on the ground.
+const GROUND_DISTANCE: f32 = 0.1;
+/// Highest drop a crouching player is protected from walking off.
+const EDGE_DROP: f32 = 0.6;
+
+const PITCH_BOUND: f32 = std::f32::consts::FRAC_PI_2 - 1E-3;
+
+/// Moves `current` towards `target` as if it was smoothed continuously with the given time
+/// constant. The result only depends on the time elapsed, not on how it is split into frames.
+fn smooth(current: Vec2, target: Vec2, time_constant: Option<f32>, dt: f32) -> Vec2 {
+    match time_constant {
+        Some(time_constant) if time_constant > 0.0 => {
+            current.lerp(target, 1.0 - (-dt / time_constant).exp())
+        }
+        _ => target,
+    }
+}
+
+type PlayerQuery<'a> = (
+    Entity,
+    &'a ActionState<PlayerInputMap>,
+    &'a mut PlayerController,
+    &'a mut KinematicCharacterController,
+    Option<&'a KinematicCharacterControllerOutput>,
+    Option<&'a mut Collider>,
+    &'a mut Transform,
+);
+
+/// Whether a player with the given half extents standing at `translation` stands on
+/// something, found by casting a thin box down from its feet. The box is a little narrower
+/// than the player, so that walls it brushes against don't count.
+fn is_grounded(
+    rapier_context: &RapierContext,
+    entity: Entity,
+    translation: Vec3,
+    half_extents: Vec3,
+) -> bool {
+    let probe = Collider::cuboid(half_extents.x * 0.95, GROUND_PROBE, half_extents.z * 0.95);
+    let filter = QueryFilter::new()
+        .exclude_rigid_body(entity)
+        .exclude_sensors();
+    rapier_context
+        .cast_shape(
+            translation - Vec3::Y * (half_extents.y - GROUND_PROBE),
+            Quat::IDENTITY,
+            -Vec3::Y,
+            &probe,
+            GROUND_DISTANCE,
+            filter,
+        )
+        .is_some()
+}
+
+/// Whether there is ground within [`EDGE_DROP`] under a player with the given half extents
+/// standing at `translation`.
+fn has_ground(
+    rapier_context: &RapierContext,
+    entity: Entity,
+    translation: Vec3,
+    half_extents: Vec3,
+) -> bool {
+    let feet = translation - Vec3::Y * (half_extents.y - GROUND_PROBE);
+    let probe = Collider::cuboid(half_extents.x, GROUND_PROBE, half_extents.z);
+    let filter = QueryFilter::new()
+        .exclude_rigid_body(entity)
+        .exclude_sensors();
+    rapier_context
+        .cast_shape(feet, Quat::IDENTITY, -Vec3::Y, &probe, EDGE_DROP, filter)
+        .is_some()
+}
+
+/// Whether a player with the given half extents fits at `translation`.
+fn has_room(
+    rapier_context: &RapierContext,
+    entity: Entity,
+    translation: Vec3,
+    half_extents: Vec3,
+) -> bool {
+    // Leave a little margin so the floor and walls the player touches don't count.
+    let half_extents = half_extents - 0.02;
+    let shape = Collider::cuboid(half_extents.x, half_extents.y, half_extents.z);
+    let filter = QueryFilter::new()
+        .exclude_rigid_body(entity)
+        .exclude_sensors();
+    rapier_context
+        .intersection_with_shape(translation, Quat::IDENTITY, &shape, filter)
+        .is_none()
+}
+
+fn control_system(
+    mut controllers: Query<PlayerQuery>,
+    cameras: Query<&PlayerCamera>,
+    rapier_context: Res<RapierContext>,
+    world: Res<VoxelWorld>,
+    mode: Res<GameMode>,
+    time: Res<Time>,
+) {
+    let (entity, action, mut controller, mut character, output, collider, mut transform) =
+        controllers.single_mut();
+    let dt = time.delta_seconds();
+
+    // The free camera takes the controls, the player stands still meanwhile.
+    let idle = ActionState::default();
+    let action = if cameras.single().mode == CameraMode::FreeFly {
+        &idle
+    } else {
+        action
+    };
+
+    let cursor_delta = -action
+        .axis_pair(PlayerInputMap::PanCamera)
+        .unwrap_or_default()
+        .xy();
+    controller.look(cursor_delta, dt);
+    // Yaw. The pitch is applied to the camera.
+    transform.rotation = Quat::from_rotation_y(controller.yaw_pitch.x);
+
+    let xz = Vec3::new(1.0, 0.0, 1.0);
+    let rotation = Quat::from_rotation_x(controller.yaw_pitch.y)
+        * Quat::from_rotation_y(controller.yaw_pitch.x);
+    let heading = Heading {
+        forward: ((rotation * -Vec3::Z) * xz).normalize(),
+        right: ((rotation * Vec3::X) * xz).normalize(),
+    };
+
+    // Crouching shrinks the body towards its feet. Standing back up needs the room for it.
+    let crouch = controller.state.can_crouch() && action.pressed(PlayerInputMap::Crouch);
+    if crouch != controller.crouching {
+        let shift = Vec3::Y * controller.crouch_depth / 2.0;
+        let standing = transform.translation + shift;
+        if crouch {
+            controller.crouching = true;
+            transform.translation -= shift;
+        } else if has_room(&rapier_context, entity, standing, PLAYER_HALF_EXTENTS) {
+            controller.crouching = false;
+            transform.translation = standing;
+        }
+        let half_extents = controller.half_extents();
+        if let Some(mut collider) = collider {
+            *collider = Collider::cuboid(half_extents.x, half_extents.y, half_extents.z);
+        }
+    }
+
+    // Speed lost against a ceiling is not kept for the next frame.
+    if let Some(output) = output {
+        if output.desired_translation.y > 0.0
+            && output.effective_translation.y < output.desired_translation.y * 0.5
+        {
+            controller.velocity.y = controller.velocity.y.min(0.0);
+        }
+    }
+
+    let grounded = is_grounded(
+        &rapier_context,
+        entity,
+        transform.translation,
+        controller.half_extents(),
+    );
+    let surroundings = surroundings(&world, &controller, transform.translation, grounded, output);
+    if action.just_pressed(PlayerInputMap::ToggleFly) && mode.can_fly() {
+        controller.state = if controller.state == MovementState::Flying {
+            MovementState::Walking
+        } else {
+            MovementState::Flying
+        };
+        controller.velocity = Vec3::ZERO;
+    }
+    controller.state = controller.state.next(&surroundings);
+    let state = controller.state;
+
+    let mut velocity = state.velocity(&mut controller, action, heading, &surroundings, dt);
+
+    // Crouching players don't walk off edges. Each axis is checked on its own, so they can
+    // still move along the edge.
+    if state == MovementState::Walking
+        && controller.crouching
+        && surroundings.grounded
+        && velocity.y <= 0.0
+    {
+        let half_extents = controller.half_extents();
+        let mut target = transform.translation;
+        for axis in [Vec3::X, Vec3::Z] {
+            let step = velocity * axis * dt;
+            if has_ground(&rapier_context, entity, target + step, half_extents) {
+                target += step;
+            } else {
+                velocity -= velocity * axis;
+            }
+        }
+    }
+    controller.velocity = velocity;
+
+    let grounded = state.sticks_to_ground();
+    character.max_slope_climb_angle = controller.max_slope;
+    character.autostep = grounded.then_some(CharacterAutostep {
+        max_height: CharacterLength::Absolute(controller.step_height),
+        min_width: CharacterLength::Absolute(0.3),
+        include_dynamic_bodies: false,
+    });
+    character.snap_to_ground = (grounded && velocity.y <= 0.0)
+        .then_some(CharacterLength::Absolute(controller.snap_distance));
+    if *mode == GameMode::Spectator {
+        // Without a collider there is nothing for the character controller to do.
+        character.translation = None;
+        transform.translation += velocity * dt;
+    } else {
+        character.translation = Some(velocity * dt);
+    }
+}
+
+/// Looks at the blocks the player is in and the outcome of its last move.
+fn surroundings(
+    world: &VoxelWorld,
+    controller: &PlayerController,
+    translation: Vec3,
+    grounded: bool,
+    output: Option<&KinematicCharacterControllerOutput>,
+) -> Surroundings {
+    let voxel = |pos: Vec3| world.get(pos.round().as_ivec3());
+    let in_fluid = |pos: Vec3| voxel(pos).and_then(Fluid::from_voxel).is_some();
+
+    let half_extents = controller.half_extents();
+    let feet = translation - Vec3::Y * (half_extents.y - 0.1);
+    let eyes = translation + controller.camera_offset();
+    let body = [feet, translation, eyes];
+
+    let blocked = output.is_some_and(|output| {
+        let xz = Vec3::new(1.0, 0.0, 1.0);
+        let desired = (output.desired_translation * xz).length();
+        let effective = (output.effective_translation * xz).length();
+        desired > 1E-4 && effective < desired * 0.5
+    });
+    Surroundings {
+        grounded,
+        blocked,
+        submerged: body.iter().filter(|&&pos| in_fluid(pos)).count() as f32 / body.len() as f32,
+        eyes_submerged: in_fluid(eyes),
+        climbable: [feet, translation]
+            .iter()
+            .any(|&pos| voxel(pos).is_some_and(|voxel| block::def(voxel).climbable)),
+    }
+}
+
+/// Blocks carried by the player. The kind of block broken last is the one placed.
+#[derive(Clone, Component, Debug)]
+struct Inventory {
+    pub counts: HashMap<VoxelID, u32>,
+    pub selected: VoxelID,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            counts: HashMap::default(),
+            selected: block::COBBLESTONE,
+        }
+    }
+}
+
+/// Block being broken in survival, and for how long.
+#[derive(Clone, Copy, Debug)]
+struct Mining {
+    target: IVec3,
+    time: f32,
+}
+
+/// Breaks, places and blows up the blocks the player looks at, as allowed by the
+/// [`GameMode`], and opens and closes doors.
+#[allow(clippy::too_many_arguments)]
+fn interaction_system(
+    mut players: Query<(
+        &ActionState<PlayerInputMap>,
+        &PlayerController,
+        &mut Inventory,
+        &Transform,
+    )>,
+    cameras: Query<&PlayerCamera>,
+    windows: Res<Windows>,
+    mut world: ResMut<VoxelWorld>,
+    mode: Res<GameMode>,
+    time: Res<Time>,
+    mut mining: Local<Option<Mining>>,
+    mut was_grabbed: Local<bool>,
+) {
+    let (action, controller, mut inventory, transform) = players.single_mut();
+    // The click grabbing the cursor back only focuses the window, whether the cursor is
+    // grabbed before or after this system runs on that frame.
+    let grabbed = windows
+        .get_primary()
+        .is_some_and(|window| window.cursor_grab_mode() != CursorGrabMode::None);
+    let focused = grabbed && *was_grabbed;
+    *was_grabbed = grabbed;
+    // The player doesn't act while the free camera flies around.
+    let active = focused && cameras.single().mode != CameraMode::FreeFly && mode.can_interact();
+    if !active || !action.pressed(PlayerInputMap::Break) {
+        *mining = None;
+    }
+    if !active {
+        return;
+    }
+    let (eye, forward) = controller.eye(transform);
+    let Some((hit, normal)) = world.raycast(eye, forward, REACH) else {
+        return;
+    };
+
+    let creative = mode.is_creative();
+    let broken = if creative {
+        action.just_pressed(PlayerInputMap::Break)
+    } else if action.pressed(PlayerInputMap::Break) {
+        // Looking at another block starts over.
+        let progress = mining.get_or_insert(Mining {
+            target: hit,
+            time: 0.0,
+        });
+        if progress.target != hit {
+            *progress = Mining {
+                target: hit,
+                time: 0.0,
+            };
+        }
+        progress.time += time.delta_seconds();
+        let hardness = world
+            .get(hit)
+            .map_or(0.0, |voxel| block::def(voxel).hardness);
+        progress.time >= hardness
+    } else {
+        false
+    };
+
+    if broken {
+        *mining = None;
+        if let Some(voxel) = world.get(hit) {
+            inventory.selected = voxel;
+            if !creative {
+                *inventory.counts.entry(voxel).or_default() += 1;
+            }
+        }
+        world.edit_box(hit, hit, None);
+    } else if action.just_pressed(PlayerInputMap::Place) {
+        // Doors are opened and closed instead of being built against.
+        if world
+            .get(hit)
+            .is_some_and(|voxel| block::def(voxel).state == StateKind::Door)
+        {
+            let state = world.state(hit);
+            world.set_state(hit, state.with_open(!state.open()));
+            return;
+        }
+        // The player's eyes are inside the block, there is no face to build against.
+        if normal == IVec3::ZERO {
+            return;
+        }
+        // Don't wall the player in.
+        let placed = hit + normal;
+        let offset = (placed.as_vec3() - transform.translation).abs();
+        if offset.cmplt(controller.half_extents() + 0.5).all() {
+            return;
+        }
+        let selected = inventory.selected;
+        if !creative {
+            match inventory.counts.get_mut(&selected) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => return,
+            }
+        }
+        world.edit_box(placed, placed, Some(selected));
+        world.set_state(
+            placed,
+            BlockState::placed(block::def(selected).state, normal, forward),
+        );
+    } else if creative && action.just_pressed(PlayerInputMap::Excavate) {
+        world.edit_sphere(hit.as_vec3(), EXCAVATE_RADIUS, None);
+    } else if creative && action.just_pressed(PlayerInputMap::Detonate) {
+        world.explode(hit.as_vec3(), EXPLOSION_POWER);
+    }
+}
+
+/// Shakes the camera while the ground rumbles.
+#[derive(Clone, Component, Copy, Debug, Default)]
+pub struct Rumble {
+    time_left: f32,
+    strength: f32,
+    /// How far the camera is shaken from where it should be.
+    offset: Vec3,
+}
+
+fn rumble_system(
+    mut cave_ins: EventReader<CaveInEvent>,
+    mut edits: EventReader<VoxelEditEvent>,
+    players: Query<&GlobalTransform, With<PlayerController>>,
+    mut rumbles: Query<&mut Rumble>,
+    time: Res<Time>,
+) {
+    let mut rumble = rumbles.single_mut();
+    let player = players.single().translation();
+
+    for event in cave_ins.iter() {
+        // Warnings rumble longer, the cave-in itself harder.
+        let (center, blocks, duration, scale) = match *event {
+            CaveInEvent::Warning { center, blocks } => (center, blocks, 2.0, 0.01),
+            CaveInEvent::Collapse { center, blocks } => (center, blocks, 1.0, 0.03),
+        };
+        let distance = center.as_vec3().distance(player);
+        if distance > RUMBLE_DISTANCE {
+            continue;
+        }
+        let strength = scale * (blocks as f32).sqrt() * (1.0 - distance / RUMBLE_DISTANCE);
+        rumble.strength = rumble.strength.max(strength);
+        rumble.time_left = rumble.time_left.max(duration);
+    }
+    for edit in edits.iter().filter(|edit| edit.kind == EditKind::Explosion) {
+        let center = (edit.min + edit.max).as_vec3() / 2.0;
+        let distance = center.distance(player);
+        if distance > RUMBLE_DISTANCE {
+            continue;
+        }
+        let strength =
+            0.05 * (edit.changes.len() as f32).sqrt() * (1.0 - distance / RUMBLE_DISTANCE);
+        rumble.strength = rumble.strength.max(strength);
+        rumble.time_left = rumble.time_left.max(0.5);
+    }
+
+    rumble.time_left = (rumble.time_left - time.delta_seconds()).max(0.0);
+    if rumble.time_left == 0.0 {
+        rumble.strength = 0.0;
+    }
+    let shake = Vec3::new(
+        rand::random::<f32>() - 0.5,
+        rand::random::<f32>() - 0.5,
+        rand::random::<f32>() - 0.5,
+    );
+    rumble.offset = shake * rumble.strength;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smooths towards a fixed target for one second, split into `steps` frames.
+    fn smooth_for_a_second(steps: u32, time_constant: Option<f32>) -> Vec2 {
+        let target = Vec2::new(1.0, -0.5);
+        let dt = 1.0 / steps as f32;
+        (0..steps).fold(Vec2::ZERO, |current, _| {
+            smooth(current, target, time_constant, dt)
+        })
+    }
+
+    #[test]
+    fn smoothing_does_not_depend_on_the_frame_rate() {
+        for time_constant in [0.075, 0.3, 1.0] {
+            let slow = smooth_for_a_second(30, Some(time_constant));
+            let fast = smooth_for_a_second(240, Some(time_constant));
+            assert!(slow.abs_diff_eq(fast, 1E-4), "{slow} != {fast}");
+        }
+    }
+
+    #[test]
+    fn smoothing_covers_two_thirds_of_the_turn_in_one_time_constant() {
+        let turned = smooth_for_a_second(60, Some(1.0));
+        let expected = Vec2::new(1.0, -0.5) * (1.0 - (-1.0_f32).exp());
+        assert!(turned.abs_diff_eq(expected, 1E-4), "{turned} != {expected}");
+    }
+
+    #[test]
+    fn no_smoothing_follows_the_target() {
+        assert_eq!(smooth_for_a_second(30, None), Vec2::new(1.0, -0.5));
+        assert_eq!(smooth_for_a_second(30, Some(0.0)), Vec2::new(1.0, -0.5));
+    }
+
+    /// Looks around at `fps` frames per second for a second, the mouse moving by `motion`
+    /// pixels in total evenly over the first half of it.
+    fn look_for_a_second(fps: u32, motion: Vec2) -> Vec2 {
+        let mut controller = PlayerController::default();
+        let dt = 1.0 / fps as f32;
+        for frame in 0..fps {
+            let cursor_delta = if frame < fps / 2 {
+                motion / (fps / 2) as f32
+            } else {
+                Vec2::ZERO
+            };
+            controller.look(cursor_delta, dt);
+        }
+        controller.yaw_pitch
+    }
+
+    #[test]
+    fn mouse_look_does_not_depend_on_the_frame_rate() {
+        // The second one turns the pitch past straight up.
+        for motion in [Vec2::new(300.0, -40.0), Vec2::new(-120.0, 250.0)] {
+            let slow = look_for_a_second(30, motion);
+            let fast = look_for_a_second(240, motion);
+            assert!(slow.abs_diff_eq(fast, 1E-3), "{slow} != {fast}");
+        }
+        let turned = look_for_a_second(30, Vec2::new(-120.0, 250.0));
+        assert!((turned.x + 1.2).abs() < 1E-3, "{turned}");
+        assert!((turned.y - PITCH_BOUND).abs() < 1E-3, "{turned}");
+    }
+}
@@ -0,0 +1,243 @@
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use super::{PlayerController, PlayerInputMap};
+
+/// How the player moves. Every state has its own physics and reads the inputs its own way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovementState {
+    /// Subject to gravity, only able to jump off the ground.
+    Walking,
+    /// Floating in a fluid, swimming up with jump and diving with crouch.
+    Swimming,
+    /// Holding on to a ladder or vine, climbing with jump or forward.
+    Climbing,
+    /// Free movement in every direction, without gravity.
+    Flying,
+}
+
+/// What the movement states need to know about the player's surroundings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Surroundings {
+    pub grounded: bool,
+    /// Whether a wall stopped the player's horizontal movement during the last frame.
+    pub blocked: bool,
+    /// Fraction of the player's body in a fluid.
+    pub submerged: f32,
+    /// Whether the camera is in a fluid.
+    pub eyes_submerged: bool,
+    /// Whether the player is inside a climbable block.
+    pub climbable: bool,
+}
+
+/// The player's heading on the horizontal plane.
+#[derive(Clone, Copy, Debug)]
+pub struct Heading {
+    pub forward: Vec3,
+    pub right: Vec3,
+}
+
+impl Heading {
+    /// Horizontal direction the movement keys point to, of unit length unless none is held.
+    fn planar(self, action: &ActionState<PlayerInputMap>) -> Vec3 {
+        [
+            (PlayerInputMap::MoveForward, self.forward),
+            (PlayerInputMap::MoveBackwards, -self.forward),
+            (PlayerInputMap::MoveRight, self.right),
+            (PlayerInputMap::MoveLeft, -self.right),
+        ]
+        .iter()
+        .filter(|(key, _)| action.pressed(*key))
+        .map(|(_, dir)| *dir)
+        .sum::<Vec3>()
+        .normalize_or_zero()
+    }
+}
+
+/// Fraction of the body that must be in a fluid for the player to swim rather than wade.
+const SWIM_DEPTH: f32 = 0.5;
+
+impl MovementState {
+    /// The state to move the player in given its surroundings. Flying is only entered and left
+    /// on request.
+    #[must_use]
+    pub fn next(self, surroundings: &Surroundings) -> Self {
+        if self == Self::Flying {
+            self
+        } else if surroundings.submerged >= SWIM_DEPTH {
+            Self::Swimming
+        } else if surroundings.climbable {
+            Self::Climbing
+        } else {
+            Self::Walking
+        }
+    }
+
+    /// Whether the player can crouch in this state. Swimming and flying players use crouch to
+    /// move down instead.
+    #[must_use]
+    pub const fn can_crouch(self) -> bool {
+        matches!(self, Self::Walking | Self::Climbing)
+    }
+
+    /// Whether the player sticks to the ground and steps onto ledges.
+    #[must_use]
+    pub const fn sticks_to_ground(self) -> bool {
+        matches!(self, Self::Walking)
+    }
+
+    /// Advances the velocity of `controller` by `dt` seconds and returns it.
+    pub fn velocity(
+        self,
+        controller: &mut PlayerController,
+        action: &ActionState<PlayerInputMap>,
+        heading: Heading,
+        surroundings: &Surroundings,
+        dt: f32,
+    ) -> Vec3 {
+        controller.sprinting = false;
+        controller.jumped = false;
+        match self {
+            Self::Walking => walk(controller, action, heading, surroundings, dt),
+            Self::Swimming => swim(controller, action, heading, surroundings, dt),
+            Self::Climbing => climb(controller, action, heading, dt),
+            Self::Flying => fly(controller, action, heading, dt),
+        }
+    }
+}
+
+/// Moves `velocity` towards `target` on the horizontal plane by at most `acceleration * dt`.
+fn steer(velocity: Vec3, target: Vec3, acceleration: f32, dt: f32) -> Vec3 {
+    let horizontal = Vec3::new(velocity.x, 0.0, velocity.z);
+    let target = Vec3::new(target.x, 0.0, target.z);
+    horizontal + (target - horizontal).clamp_length_max(acceleration * dt) + Vec3::Y * velocity.y
+}
+
+fn walk(
+    controller: &mut PlayerController,
+    action: &ActionState<PlayerInputMap>,
+    heading: Heading,
+    surroundings: &Surroundings,
+    dt: f32,
+) -> Vec3 {
+    let direction = heading.planar(action);
+    controller.sprinting = !controller.crouching
+        && controller.can_sprint
+        && action.pressed(PlayerInputMap::Run)
+        && direction != Vec3::ZERO;
+    let speed = if controller.crouching {
+        controller.crouch_speed
+    } else if controller.sprinting {
+        controller.run_speed
+    } else {
+        controller.walk_speed
+    };
+    let acceleration = if surroundings.grounded {
+        controller.acceleration
+    } else {
+        controller.acceleration * controller.air_control
+    };
+    let mut velocity = steer(controller.velocity, direction * speed, acceleration, dt);
+    velocity.y = controller.gravity.mul_add(-dt, velocity.y);
+
+    if surroundings.grounded {
+        controller.time_since_grounded = 0.0;
+        // Keep pressing into the ground rather than building up falling speed.
+        velocity.y = velocity.y.max(-controller.gravity * dt);
+    } else {
+        controller.time_since_grounded += dt;
+    }
+    // Holding the key down doesn't keep jumping on landing.
+    if action.just_pressed(PlayerInputMap::Jump)
+        && controller.time_since_grounded <= controller.coyote_time
+    {
+        velocity.y = controller.jump_speed;
+        controller.jumped = true;
+        // A jump uses up the remaining coyote time.
+        controller.time_since_grounded = f32::INFINITY;
+    }
+    velocity
+}
+
+fn swim(
+    controller: &mut PlayerController,
+    action: &ActionState<PlayerInputMap>,
+    heading: Heading,
+    surroundings: &Surroundings,
+    dt: f32,
+) -> Vec3 {
+    let mut velocity = steer(
+        controller.velocity,
+        heading.planar(action) * controller.swim_speed,
+        controller.acceleration,
+        dt,
+    );
+
+    // Buoyancy grows with the part of the body under the surface, so a player swimming up
+    // bobs there instead of leaving the fluid.
+    let buoyancy = controller.buoyancy.mul_add(surroundings.submerged, -1.0);
+    velocity.y = (controller.gravity * buoyancy).mul_add(dt, velocity.y);
+    if action.pressed(PlayerInputMap::Jump) {
+        velocity.y = controller.swim_stroke.mul_add(dt, velocity.y);
+    }
+    if action.pressed(PlayerInputMap::Crouch) {
+        velocity.y = controller.swim_stroke.mul_add(-dt, velocity.y);
+    }
+    velocity.y *= (-controller.water_drag * dt).exp();
+
+    // Swimming against the shore with the head out of the fluid climbs onto it.
+    if !surroundings.eyes_submerged && surroundings.blocked && action.pressed(PlayerInputMap::Jump)
+    {
+        velocity.y = velocity.y.max(controller.jump_speed);
+    }
+    controller.time_since_grounded = f32::INFINITY;
+    velocity
+}
+
+fn climb(
+    controller: &mut PlayerController,
+    action: &ActionState<PlayerInputMap>,
+    heading: Heading,
+    dt: f32,
+) -> Vec3 {
+    let mut velocity = steer(
+        controller.velocity,
+        heading.planar(action) * controller.climb_speed,
+        controller.acceleration,
+        dt,
+    );
+    // Crouching holds on, letting go slides back down.
+    velocity.y =
+        if action.pressed(PlayerInputMap::Jump) || action.pressed(PlayerInputMap::MoveForward) {
+            controller.climb_speed
+        } else if controller.crouching {
+            0.0
+        } else {
+            -controller.climb_speed
+        };
+    controller.time_since_grounded = 0.0;
+    velocity
+}
+
+fn fly(
+    controller: &PlayerController,
+    action: &ActionState<PlayerInputMap>,
+    heading: Heading,
+    dt: f32,
+) -> Vec3 {
+    let mut direction = heading.planar(action);
+    if action.pressed(PlayerInputMap::Jump) {
+        direction += Vec3::Y;
+    }
+    if action.pressed(PlayerInputMap::Crouch) {
+        direction -= Vec3::Y;
+    }
+    let speed = if action.pressed(PlayerInputMap::Run) {
+        controller.run_speed
+    } else {
+        controller.walk_speed
+    };
+    let target = direction.normalize_or_zero() * speed;
+    controller.velocity
+        + (target - controller.velocity).clamp_length_max(controller.acceleration * dt)
+}
@@ -0,0 +1,219 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+
+use super::{game_mode::GameMode, PlayerController};
+
+/// Asset path of the data file the [`StaminaSettings`] are read from.
+pub const SETTINGS_PATH: &str = "config/stamina.cfg";
+
+/// How stamina is used and regained, read from [`SETTINGS_PATH`].
+#[derive(Clone, Copy, Debug, Resource, TypeUuid)]
+#[uuid = "5b0f3f9e-8a3c-4d36-9a51-2f6d7c1e4b82"]
+pub struct StaminaSettings {
+    /// Stamina of a rested player.
+    pub max: f32,
+    /// Used per second of running.
+    pub run_cost: f32,
+    /// Used per jump.
+    pub jump_cost: f32,
+    /// Regained per second once rested.
+    pub regeneration: f32,
+    /// Seconds without running or jumping before stamina regenerates.
+    pub regeneration_delay: f32,
+    /// Stamina needed to run again after running out.
+    pub sprint_threshold: f32,
+}
+
+impl Default for StaminaSettings {
+    fn default() -> Self {
+        Self {
+            max: 100.0,
+            run_cost: 15.0,
+            jump_cost: 8.0,
+            regeneration: 20.0,
+            regeneration_delay: 1.0,
+            sprint_threshold: 25.0,
+        }
+    }
+}
+
+impl StaminaSettings {
+    /// Reads the settings from `name = value` lines, where `#` starts a comment. Settings
+    /// missing from `contents` keep their default value. Also returns what is wrong with the
+    /// lines that were skipped.
+    #[must_use]
+    pub fn parse(contents: &str) -> (Self, Vec<String>) {
+        let mut settings = Self::default();
+        let mut problems = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut problem = |problem: String| {
+                problems.push(format!("line {}: {problem}", number + 1));
+            };
+            let Some((name, value)) = line.split_once('=') else {
+                problem("expected `name = value`".to_owned());
+                continue;
+            };
+            let Ok(value) = value.trim().parse::<f32>() else {
+                problem("invalid number".to_owned());
+                continue;
+            };
+            let setting = match name.trim() {
+                "max" => &mut settings.max,
+                "run_cost" => &mut settings.run_cost,
+                "jump_cost" => &mut settings.jump_cost,
+                "regeneration" => &mut settings.regeneration,
+                "regeneration_delay" => &mut settings.regeneration_delay,
+                "sprint_threshold" => &mut settings.sprint_threshold,
+                name => {
+                    problem(format!("unknown setting `{name}`"));
+                    continue;
+                }
+            };
+            *setting = value;
+        }
+        (settings, problems)
+    }
+}
+
+/// Loads [`StaminaSettings`] from `.cfg` files.
+#[derive(Default)]
+pub struct StaminaSettingsLoader;
+
+impl AssetLoader for StaminaSettingsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let (settings, problems) = StaminaSettings::parse(std::str::from_utf8(bytes)?);
+            for problem in problems {
+                warn!("{}, {problem}", load_context.path().display());
+            }
+            load_context.set_default_asset(LoadedAsset::new(settings));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["cfg"]
+    }
+}
+
+/// Used up by running and jumping in survival. Running out of it stops the player from
+/// running until it regenerates up to [`StaminaSettings::sprint_threshold`].
+#[derive(Clone, Component, Copy, Debug)]
+pub struct Stamina {
+    pub current: f32,
+    /// Seconds since stamina was last used.
+    rested_for: f32,
+    exhausted: bool,
+}
+
+impl Stamina {
+    #[must_use]
+    pub const fn new(settings: &StaminaSettings) -> Self {
+        Self {
+            current: settings.max,
+            rested_for: 0.0,
+            exhausted: false,
+        }
+    }
+}
+
+pub fn stamina_system(
+    mut players: Query<(&mut Stamina, &mut PlayerController)>,
+    settings: Res<StaminaSettings>,
+    mode: Res<GameMode>,
+    time: Res<Time>,
+) {
+    let (mut stamina, mut controller) = players.single_mut();
+    let dt = time.delta_seconds();
+
+    // Only survival players get tired.
+    let mut cost = 0.0;
+    if *mode == GameMode::Survival {
+        if controller.sprinting {
+            cost = settings.run_cost.mul_add(dt, cost);
+        }
+        if controller.jumped {
+            cost += settings.jump_cost;
+        }
+    }
+
+    if cost > 0.0 {
+        stamina.current = (stamina.current - cost).max(0.0);
+        stamina.rested_for = 0.0;
+    } else {
+        stamina.rested_for += dt;
+        if stamina.rested_for >= settings.regeneration_delay {
+            stamina.current = settings
+                .regeneration
+                .mul_add(dt, stamina.current)
+                .min(settings.max);
+        }
+    }
+
+    if stamina.current <= 0.0 {
+        stamina.exhausted = true;
+    } else if stamina.current >= settings.sprint_threshold.min(settings.max) {
+        stamina.exhausted = false;
+    }
+    controller.can_sprint = !stamina.exhausted;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_read_by_name() {
+        let (settings, problems) = StaminaSettings::parse(
+            "# Comment\n\
+             max = 50\n\
+             \n\
+             run_cost=2.5   # trailing comment\n",
+        );
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(settings.max, 50.0);
+        assert_eq!(settings.run_cost, 2.5);
+        // The rest keep their default.
+        assert_eq!(settings.jump_cost, StaminaSettings::default().jump_cost);
+    }
+
+    #[test]
+    fn invalid_lines_are_skipped_and_reported() {
+        let (settings, problems) = StaminaSettings::parse(
+            "max 50\n\
+             jump_cost = lots\n\
+             speed = 3\n\
+             regeneration = 4\n",
+        );
+        assert_eq!(
+            problems,
+            [
+                "line 1: expected `name = value`",
+                "line 2: invalid number",
+                "line 3: unknown setting `speed`",
+            ]
+        );
+        assert_eq!(settings.max, StaminaSettings::default().max);
+        assert_eq!(settings.jump_cost, StaminaSettings::default().jump_cost);
+        assert_eq!(settings.regeneration, 4.0);
+    }
+
+    #[test]
+    fn shipped_settings_parse_cleanly() {
+        let contents = include_str!("../../../assets/config/stamina.cfg");
+        let (_, problems) = StaminaSettings::parse(contents);
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+}
@@ -1,7 +1,9 @@
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContext};
 use iyes_loopless::prelude::*;
-use iyes_progress::prelude::*;
+use iyes_progress::{prelude::*, ProgressSystemLabel};
+
+use super::player::stamina::{self, StaminaSettings};
 
 pub struct GameResourcePlugin;
 
@@ -9,6 +11,9 @@ pub struct GameResourcePlugin;
 pub struct GameResources {
     pub font: Handle<Font>,
     pub block_textures: Handle<Image>,
+    /// Frame strips of the animated blocks, in [`super::terrain::block::animated`] order.
+    pub animated_textures: Vec<Handle<Image>>,
+    pub stamina_settings: Handle<StaminaSettings>,
 }
 
 impl Plugin for GameResourcePlugin {
@@ -34,11 +39,21 @@ fn load_assets(
 ) {
     let font = asset_server.load("fonts/Iosevka NF.ttf");
     let block_textures = asset_server.load("textures/blocks/grass_block_top.ktx2");
+    let animated_textures = super::terrain::block::animated()
+        .map(|(_, animation)| asset_server.load(animation.texture))
+        .collect::<Vec<Handle<Image>>>();
+    let stamina_settings = asset_server.load(stamina::SETTINGS_PATH);
     loading.add(&font);
     loading.add(&block_textures);
+    for texture in &animated_textures {
+        loading.add(texture);
+    }
+    loading.add(&stamina_settings);
     commands.insert_resource(GameResources {
         font,
         block_textures,
+        animated_textures,
+        stamina_settings,
     });
 }
 
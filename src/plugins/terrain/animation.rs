@@ -0,0 +1,91 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension},
+};
+
+use super::block::Animation;
+
+struct AnimatedTexture {
+    strip: Handle<Image>,
+    /// The texture the material samples, holding the current frame of the strip.
+    frame: Handle<Image>,
+    animation: Animation,
+    current: u32,
+}
+
+/// Animated block textures. Frames are copied out of their strips into the textures used by
+/// the chunk materials, so chunks never need to be remeshed to animate.
+#[derive(Default, Resource)]
+pub struct TextureAnimations {
+    textures: Vec<AnimatedTexture>,
+}
+
+impl TextureAnimations {
+    /// Starts animating a loaded strip. Returns the texture to draw the animation with.
+    pub fn insert(
+        &mut self,
+        images: &mut Assets<Image>,
+        strip: Handle<Image>,
+        animation: Animation,
+    ) -> Handle<Image> {
+        let frame = images.get(&strip).map_or_else(Image::default, |image| {
+            let size = image.texture_descriptor.size;
+            let mut frame = Image::new(
+                Extent3d {
+                    width: size.width,
+                    height: size.height / animation.frames,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                Self::frame_data(image, animation.frames, 0).to_vec(),
+                image.texture_descriptor.format,
+            );
+            frame.sampler_descriptor = image.sampler_descriptor.clone();
+            frame
+        });
+        let frame = images.add(frame);
+        self.textures.push(AnimatedTexture {
+            strip,
+            frame: frame.clone(),
+            animation,
+            current: 0,
+        });
+        frame
+    }
+
+    /// The bytes of the `index`th frame. Frames are whole rows of the strip, so they are
+    /// contiguous in any uncompressed format.
+    fn frame_data(strip: &Image, frames: u32, index: u32) -> &[u8] {
+        let len = strip.data.len() / frames as usize;
+        &strip.data[len * index as usize..len * (index as usize + 1)]
+    }
+}
+
+pub fn animation_system(
+    time: Res<Time>,
+    mut animations: ResMut<TextureAnimations>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for texture in &mut animations.textures {
+        let animation = texture.animation;
+        let index = (time.elapsed_seconds() / animation.frame_time) as u32 % animation.frames;
+        if index == texture.current {
+            continue;
+        }
+
+        let Some(data) = images
+            .get(&texture.strip)
+            .map(|strip| TextureAnimations::frame_data(strip, animation.frames, index).to_vec())
+        else {
+            continue;
+        };
+        // A strip that wasn't loaded at startup left a placeholder of a different size.
+        if let Some(frame) = images
+            .get_mut(&texture.frame)
+            .filter(|frame| frame.data.len() == data.len())
+        {
+            frame.data = data;
+            texture.current = index;
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use super::{
+    block_entity::BlockEntityKind, shape::Shape, state::StateKind, texture::TextureRule,
+    world::VoxelID,
+};
+
+pub const STONE: VoxelID = 1;
+pub const WATER: VoxelID = 2;
+pub const LAVA: VoxelID = 3;
+pub const OBSIDIAN: VoxelID = 4;
+pub const COBBLESTONE: VoxelID = 5;
+pub const LOG: VoxelID = 6;
+pub const FURNACE: VoxelID = 7;
+pub const DOOR: VoxelID = 8;
+pub const WHEAT: VoxelID = 9;
+pub const SLAB: VoxelID = 10;
+pub const STAIRS: VoxelID = 11;
+pub const FENCE: VoxelID = 12;
+pub const TORCH: VoxelID = 13;
+pub const LEAVES: VoxelID = 14;
+pub const GLASS: VoxelID = 15;
+pub const GLOWING_ORE: VoxelID = 16;
+pub const CHEST: VoxelID = 17;
+pub const SIGN: VoxelID = 18;
+pub const SAND: VoxelID = 19;
+pub const GRAVEL: VoxelID = 20;
+pub const LADDER: VoxelID = 21;
+pub const VINE: VoxelID = 22;
+
+/// Which pass a block is drawn in. Each chunk has one mesh per layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderLayer {
+    Opaque,
+    /// Alpha-tested: texels are either fully drawn or discarded, like leaves and plants.
+    Cutout,
+    /// Alpha-blended, like water. Drawn after the other layers.
+    Translucent,
+}
+
+impl RenderLayer {
+    pub const ALL: [Self; 3] = [Self::Opaque, Self::Cutout, Self::Translucent];
+}
+
+/// An animated texture, stored as a strip of square frames stacked from top to bottom. A
+/// strip of a single frame gives a block a still texture of its own.
+#[derive(Clone, Copy, Debug)]
+pub struct Animation {
+    /// Asset path of the strip. It must be uncompressed, as frames are copied out of it.
+    pub texture: &'static str,
+    pub frames: u32,
+    /// Seconds each frame is shown for.
+    pub frame_time: f32,
+}
+
+/// Static properties shared by every voxel of a given ID.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockDef {
+    /// Whether light passes through the block.
+    pub transparent: bool,
+    /// Block light emitted by the block, up to [`super::light::MAX_LIGHT`].
+    pub emission: u8,
+    /// How the block's [`super::state::BlockState`] is interpreted.
+    pub state: StateKind,
+    pub shape: Shape,
+    /// Whether entities collide with the block.
+    pub solid: bool,
+    pub layer: RenderLayer,
+    /// Blocks with an animation are drawn with their own texture instead of the shared one.
+    pub animation: Option<Animation>,
+    /// Rules picking several tiles need a texture laid out for them, so they are only used
+    /// with blocks' own textures, or to turn the shared one.
+    pub texture: TextureRule,
+    /// Blocks with a kind get a [`super::block_entity::BlockEntity`] spawned along with them.
+    pub block_entity: Option<BlockEntityKind>,
+    /// Whether the block falls when there is nothing under it.
+    pub gravity: bool,
+    /// Support the block passes on to the ones it holds, see [`super::support`]. Blocks
+    /// without one never cave in.
+    pub support: Option<u8>,
+    /// How much of an explosion's strength the block absorbs before breaking.
+    pub blast_resistance: f32,
+    /// Whether entities inside the block can climb it, like ladders.
+    pub climbable: bool,
+    /// Seconds it takes to break the block by hand in survival.
+    pub hardness: f32,
+}
+
+impl BlockDef {
+    const SOLID: Self = Self {
+        transparent: false,
+        emission: 0,
+        state: StateKind::None,
+        shape: Shape::Cube,
+        solid: true,
+        layer: RenderLayer::Opaque,
+        animation: None,
+        texture: TextureRule::Fixed,
+        block_entity: None,
+        gravity: false,
+        support: Some(4),
+        blast_resistance: 3.0,
+        climbable: false,
+        hardness: 1.5,
+    };
+    const FLUID: Self = Self {
+        transparent: true,
+        solid: false,
+        layer: RenderLayer::Translucent,
+        support: None,
+        // Fluids are never destroyed, but soak up blasts.
+        blast_resistance: 100.0,
+        hardness: f32::INFINITY,
+        ..Self::SOLID
+    };
+    /// A partial model that doesn't block light.
+    const PARTIAL: Self = Self {
+        transparent: true,
+        support: None,
+        blast_resistance: 1.0,
+        hardness: 1.0,
+        ..Self::SOLID
+    };
+    /// A full block with see-through texels, like leaves or glass.
+    const CUTOUT: Self = Self {
+        transparent: true,
+        layer: RenderLayer::Cutout,
+        support: Some(1),
+        blast_resistance: 0.3,
+        hardness: 0.3,
+        ..Self::SOLID
+    };
+    /// A thin panel against the block north of it that entities climb instead of colliding
+    /// with.
+    const CLIMBABLE: Self = Self {
+        state: StateKind::Facing,
+        shape: Shape::Door,
+        solid: false,
+        layer: RenderLayer::Cutout,
+        climbable: true,
+        blast_resistance: 0.4,
+        hardness: 0.4,
+        ..Self::PARTIAL
+    };
+    /// A loose block falling when unsupported.
+    const LOOSE: Self = Self {
+        gravity: true,
+        support: None,
+        blast_resistance: 0.5,
+        hardness: 0.6,
+        ..Self::ROTATED
+    };
+    /// A natural block whose texture is turned at random, breaking up repetition.
+    const ROTATED: Self = Self {
+        texture: TextureRule::Random {
+            rotate: true,
+            variants: 1,
+        },
+        blast_resistance: 6.0,
+        ..Self::SOLID
+    };
+}
+
+/// Indexed by [`VoxelID`]. Index 0 is a placeholder used for unknown IDs.
+const BLOCKS: [BlockDef; 23] = {
+    let mut blocks = [BlockDef::SOLID; 23];
+    blocks[STONE as usize] = BlockDef::ROTATED;
+    blocks[WATER as usize] = BlockDef {
+        animation: Some(Animation {
+            texture: "textures/blocks/water_still.png",
+            frames: 32,
+            frame_time: 0.1,
+        }),
+        ..BlockDef::FLUID
+    };
+    blocks[LAVA as usize] = BlockDef {
+        emission: 15,
+        layer: RenderLayer::Opaque,
+        animation: Some(Animation {
+            texture: "textures/blocks/lava_still.png",
+            frames: 20,
+            frame_time: 0.15,
+        }),
+        ..BlockDef::FLUID
+    };
+    blocks[OBSIDIAN as usize] = BlockDef {
+        support: Some(8),
+        blast_resistance: 1200.0,
+        hardness: 50.0,
+        ..BlockDef::ROTATED
+    };
+    blocks[COBBLESTONE as usize] = BlockDef {
+        support: Some(2),
+        hardness: 2.0,
+        ..BlockDef::ROTATED
+    };
+    blocks[LOG as usize] = BlockDef {
+        state: StateKind::Axis,
+        support: Some(6),
+        blast_resistance: 2.0,
+        hardness: 2.0,
+        ..BlockDef::SOLID
+    };
+    blocks[FURNACE as usize] = BlockDef {
+        state: StateKind::Facing,
+        block_entity: Some(BlockEntityKind::Furnace),
+        ..BlockDef::SOLID
+    };
+    blocks[DOOR as usize] = BlockDef {
+        state: StateKind::Door,
+        shape: Shape::Door,
+        ..BlockDef::PARTIAL
+    };
+    blocks[WHEAT as usize] = BlockDef {
+        state: StateKind::Crop,
+        shape: Shape::Cross,
+        solid: false,
+        layer: RenderLayer::Cutout,
+        hardness: 0.0,
+        ..BlockDef::PARTIAL
+    };
+    blocks[SLAB as usize] = BlockDef {
+        shape: Shape::Slab,
+        ..BlockDef::PARTIAL
+    };
+    blocks[STAIRS as usize] = BlockDef {
+        state: StateKind::Facing,
+        shape: Shape::Stairs,
+        ..BlockDef::PARTIAL
+    };
+    blocks[FENCE as usize] = BlockDef {
+        shape: Shape::Fence,
+        ..BlockDef::PARTIAL
+    };
+    blocks[TORCH as usize] = BlockDef {
+        emission: 14,
+        shape: Shape::Torch,
+        solid: false,
+        layer: RenderLayer::Cutout,
+        hardness: 0.0,
+        ..BlockDef::PARTIAL
+    };
+    blocks[LEAVES as usize] = BlockDef {
+        texture: BlockDef::ROTATED.texture,
+        support: None,
+        blast_resistance: 0.2,
+        hardness: 0.2,
+        ..BlockDef::CUTOUT
+    };
+    blocks[GLASS as usize] = BlockDef {
+        animation: Some(Animation {
+            texture: "textures/blocks/glass.png",
+            frames: 1,
+            frame_time: f32::INFINITY,
+        }),
+        texture: TextureRule::Connected,
+        ..BlockDef::CUTOUT
+    };
+    blocks[GLOWING_ORE as usize] = BlockDef {
+        emission: 7,
+        animation: Some(Animation {
+            texture: "textures/blocks/glowing_ore.png",
+            frames: 4,
+            frame_time: 0.5,
+        }),
+        texture: TextureRule::Connected,
+        ..BlockDef::SOLID
+    };
+    blocks[CHEST as usize] = BlockDef {
+        state: StateKind::Facing,
+        block_entity: Some(BlockEntityKind::Chest),
+        ..BlockDef::SOLID
+    };
+    blocks[SIGN as usize] = BlockDef {
+        state: StateKind::Facing,
+        shape: Shape::Sign,
+        solid: false,
+        block_entity: Some(BlockEntityKind::Sign),
+        ..BlockDef::PARTIAL
+    };
+    blocks[SAND as usize] = BlockDef::LOOSE;
+    blocks[GRAVEL as usize] = BlockDef::LOOSE;
+    blocks[LADDER as usize] = BlockDef::CLIMBABLE;
+    blocks[VINE as usize] = BlockDef {
+        blast_resistance: 0.2,
+        hardness: 0.2,
+        ..BlockDef::CLIMBABLE
+    };
+    blocks
+};
+
+#[must_use]
+pub fn def(voxel: VoxelID) -> &'static BlockDef {
+    usize::try_from(voxel)
+        .ok()
+        .and_then(|i| BLOCKS.get(i))
+        .unwrap_or(&BLOCKS[0])
+}
+
+/// Every block with an animated texture, in ID order.
+pub fn animated() -> impl Iterator<Item = (VoxelID, &'static Animation)> {
+    BLOCKS
+        .iter()
+        .zip(0..)
+        .filter_map(|(def, voxel)| def.animation.as_ref().map(|animation| (voxel, animation)))
+}
+
+/// Number of meshes each chunk is split into: one per [`RenderLayer`], then one per
+/// animated block so that it can be drawn with its own texture.
+#[must_use]
+pub fn mesh_slots() -> usize {
+    RenderLayer::ALL.len() + animated().count()
+}
+
+/// Index of the chunk mesh the given voxel is drawn in, out of [`mesh_slots`].
+#[must_use]
+pub fn mesh_slot(voxel: VoxelID) -> usize {
+    let def = def(voxel);
+    if def.animation.is_none() {
+        return def.layer as usize;
+    }
+    RenderLayer::ALL.len() + animated().take_while(|&(id, _)| id != voxel).count()
+}
+
+/// Whether the face of `voxel` pressed against `neighbour` is hidden by it. Only full
+/// opaque blocks hide everything; see-through blocks only hide faces of their own kind, so
+/// glass next to glass shows no inner faces while stone behind glass is still drawn.
+#[must_use]
+pub fn hides(voxel: VoxelID, neighbour: VoxelID) -> bool {
+    let def = def(neighbour);
+    def.shape.is_full() && (def.layer == RenderLayer::Opaque || neighbour == voxel)
+}
+
+/// Whether light passes through the given voxel. Air is always transparent.
+#[must_use]
+pub fn is_transparent(voxel: Option<VoxelID>) -> bool {
+    voxel.is_none_or(|voxel| def(voxel).transparent)
+}
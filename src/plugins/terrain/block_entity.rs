@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+
+use super::{
+    block,
+    world::{VoxelID, VoxelWorld},
+};
+
+/// The kind of block entity a block declares in its [`block::BlockDef`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockEntityKind {
+    Chest,
+    Furnace,
+    Sign,
+}
+
+/// Ties an entity to the voxel it belongs to. It is spawned when the block is placed and
+/// despawned when it is replaced.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct BlockEntity(pub IVec3);
+
+/// State of a block entity that doesn't fit in a voxel. It is all that is kept of the
+/// entity while its chunk is compressed. It only lives in memory and is never saved.
+#[derive(Component, Clone, Debug, Reflect, FromReflect)]
+pub enum BlockEntityData {
+    /// Stacks of items, as voxel IDs and counts.
+    Chest {
+        items: Vec<(VoxelID, u32)>,
+    },
+    /// Ticks of fuel left, and ticks spent smelting the current item.
+    Furnace {
+        fuel: u32,
+        progress: u32,
+    },
+    Sign {
+        text: String,
+    },
+}
+
+impl BlockEntityData {
+    #[must_use]
+    pub const fn new(kind: BlockEntityKind) -> Self {
+        match kind {
+            BlockEntityKind::Chest => Self::Chest { items: Vec::new() },
+            BlockEntityKind::Furnace => Self::Furnace {
+                fuel: 0,
+                progress: 0,
+            },
+            BlockEntityKind::Sign => Self::Sign {
+                text: String::new(),
+            },
+        }
+    }
+}
+
+/// Spawns the block entity of the voxel at `pos` and registers it with the world.
+pub fn spawn(commands: &mut Commands, world: &mut VoxelWorld, pos: IVec3, data: BlockEntityData) {
+    let entity = commands
+        .spawn((BlockEntity(pos), data, Name::new("Block Entity")))
+        .id();
+    world.insert_block_entity(pos, entity);
+}
+
+/// Creates and destroys block entities along with their blocks, and respawns those of
+/// decompressed chunks.
+pub fn block_entity_system(mut commands: Commands, mut world: ResMut<VoxelWorld>) {
+    for (pos, data) in world.take_restored_block_entities() {
+        spawn(&mut commands, &mut world, pos, data);
+    }
+    for pos in world.take_block_entity_changes() {
+        if let Some(entity) = world.remove_block_entity(pos) {
+            commands.entity(entity).despawn_recursive();
+        }
+        if let Some(kind) = world
+            .get(pos)
+            .and_then(|voxel| block::def(voxel).block_entity)
+        {
+            spawn(&mut commands, &mut world, pos, BlockEntityData::new(kind));
+        }
+    }
+}
+
+/// Runs the per-tick logic of block entities.
+pub fn tick_system(
+    world: Res<VoxelWorld>,
+    mut block_entities: Query<(Entity, &BlockEntity, &mut BlockEntityData)>,
+) {
+    for (entity, block_entity, mut data) in &mut block_entities {
+        // Skip entities whose block was replaced but that are not despawned yet.
+        if world.block_entity(block_entity.0) != Some(entity) {
+            continue;
+        }
+        let BlockEntityData::Furnace { fuel, progress } = *data else {
+            continue;
+        };
+        // Smelting only advances while there is fuel, and is lost once it runs out.
+        if fuel > 0 {
+            *data = BlockEntityData::Furnace {
+                fuel: fuel - 1,
+                progress: progress + 1,
+            };
+        } else if progress > 0 {
+            *data = BlockEntityData::Furnace {
+                fuel: 0,
+                progress: 0,
+            };
+        }
+    }
+}
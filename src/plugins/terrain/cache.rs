@@ -0,0 +1,252 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+    utils::HashMap,
+};
+
+use super::{
+    block_entity::BlockEntityData,
+    world::{Cell, Chunk, VoxelWorld, CHUNK_HEIGHT, CHUNK_WIDTH},
+};
+use crate::plugins::player::PlayerController;
+
+/// Bytes held by chunk data, compressed or not.
+pub const CHUNK_MEMORY: DiagnosticId =
+    DiagnosticId::from_u128(0x3c2b_7e0e_9f41_4d8a_b6a5_1f0c_2d9e_8a47);
+
+/// Keeps the memory used by chunks within a budget by compressing the ones that haven't
+/// been near the player for the longest time.
+///
+/// Compressed chunks stay in the [`VoxelWorld`], which keeps reading from them and
+/// decompresses them as soon as they are modified or the player comes near again. Their
+/// block entities are despawned while they are compressed, and respawned from their saved
+/// data afterwards.
+#[derive(Resource)]
+pub struct ChunkCache {
+    /// Uncompressed chunks past this many bytes are compressed, least recently used first.
+    pub budget: usize,
+    /// Chunks closer than this to the player, in blocks, are never compressed.
+    pub keep_distance: f32,
+    last_used: HashMap<IVec2, u64>,
+    frame: u64,
+}
+
+impl Default for ChunkCache {
+    fn default() -> Self {
+        Self {
+            budget: 64 * 1024 * 1024,
+            keep_distance: 64.0,
+            last_used: HashMap::default(),
+            frame: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Run {
+    /// Height the run reaches in its column, exclusive. It starts where the previous one
+    /// ends.
+    end: u16,
+    cell: Cell,
+}
+
+/// Run-length encoded chunk data. Columns are stored bottom to top, so the long vertical
+/// runs of stone and air each collapse into a few entries, and a voxel is found with a
+/// binary search in its column.
+pub struct CompressedChunk {
+    runs: Vec<Run>,
+    /// Index of the first run of every column, followed by the number of runs.
+    columns: Vec<u32>,
+    block_entities: Vec<(IVec3, BlockEntityData)>,
+}
+
+impl CompressedChunk {
+    #[must_use]
+    pub fn compress(chunk: &Chunk, block_entities: Vec<(IVec3, BlockEntityData)>) -> Self {
+        let mut runs = Vec::<Run>::new();
+        let mut columns = Vec::with_capacity(CHUNK_WIDTH * CHUNK_WIDTH + 1);
+        for (x, z) in Self::columns() {
+            columns.push(runs.len() as u32);
+            for y in 0..CHUNK_HEIGHT {
+                let cell = chunk.cell(x, y, z);
+                match runs.last_mut() {
+                    Some(last) if y > 0 && last.cell == cell => last.end += 1,
+                    _ => runs.push(Run {
+                        end: y as u16 + 1,
+                        cell,
+                    }),
+                }
+            }
+        }
+        columns.push(runs.len() as u32);
+        Self {
+            runs,
+            columns,
+            block_entities,
+        }
+    }
+
+    #[must_use]
+    pub fn decompress(&self) -> Chunk {
+        let mut chunk = Chunk::new();
+        for (x, z) in Self::columns() {
+            let mut start = 0;
+            for run in self.column(x, z) {
+                let end = usize::from(run.end);
+                for y in start..end {
+                    chunk.set_cell(x, y, z, run.cell);
+                }
+                start = end;
+            }
+        }
+        chunk
+    }
+
+    /// Everything stored for the voxel at the given local position.
+    #[must_use]
+    pub fn cell(&self, x: usize, y: usize, z: usize) -> Cell {
+        let runs = self.column(x, z);
+        runs[runs.partition_point(|run| usize::from(run.end) <= y)].cell
+    }
+
+    /// The saved data of the block entities of the chunk.
+    #[must_use]
+    pub fn into_block_entities(self) -> Vec<(IVec3, BlockEntityData)> {
+        self.block_entities
+    }
+
+    #[must_use]
+    pub const fn memory_size(&self) -> usize {
+        self.runs.len() * std::mem::size_of::<Run>()
+            + self.columns.len() * std::mem::size_of::<u32>()
+    }
+
+    fn column(&self, x: usize, z: usize) -> &[Run] {
+        let column = x * CHUNK_WIDTH + z;
+        &self.runs[self.columns[column] as usize..self.columns[column + 1] as usize]
+    }
+
+    fn columns() -> impl Iterator<Item = (usize, usize)> {
+        (0..CHUNK_WIDTH).flat_map(|x| (0..CHUNK_WIDTH).map(move |z| (x, z)))
+    }
+}
+
+pub fn setup_diagnostic(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(CHUNK_MEMORY, "chunk_memory", 20).with_suffix(" B"));
+}
+
+pub fn cache_system(
+    mut commands: Commands,
+    mut cache: ResMut<ChunkCache>,
+    mut world: ResMut<VoxelWorld>,
+    mut diagnostics: ResMut<Diagnostics>,
+    players: Query<&GlobalTransform, With<PlayerController>>,
+    block_entities: Query<&BlockEntityData>,
+) {
+    let Ok(player) = players.get_single() else {
+        return;
+    };
+    let player = Vec2::new(player.translation().x, player.translation().z);
+    cache.frame += 1;
+    let frame = cache.frame;
+
+    let near = world
+        .chunks
+        .keys()
+        .copied()
+        .chain(world.compressed_chunks().map(|(pos, _)| pos))
+        .filter(|pos| {
+            let center = (pos.as_vec2() + 0.5) * CHUNK_WIDTH as f32 - 0.5;
+            center.distance(player) < cache.keep_distance
+        })
+        .collect::<Vec<_>>();
+    for pos in near {
+        cache.last_used.insert(pos, frame);
+        world.decompress(pos);
+    }
+
+    let mut loaded = world.chunks.keys().copied().collect::<Vec<_>>();
+    loaded.sort_by_key(|pos| {
+        (
+            cache.last_used.get(pos).copied().unwrap_or_default(),
+            pos.x,
+            pos.y,
+        )
+    });
+    let mut held = loaded.len() * Chunk::MEMORY_SIZE;
+    for pos in loaded {
+        if held <= cache.budget || cache.last_used.get(&pos) == Some(&frame) {
+            break;
+        }
+        let mut saved = Vec::new();
+        for (pos, entity) in world.chunk_block_entities(pos) {
+            world.remove_block_entity(pos);
+            if let Ok(data) = block_entities.get(entity) {
+                saved.push((pos, data.clone()));
+            }
+            commands.entity(entity).despawn_recursive();
+        }
+        world.compress(pos, saved);
+        held -= Chunk::MEMORY_SIZE;
+    }
+
+    diagnostics.add_measurement(CHUNK_MEMORY, || {
+        (held
+            + world
+                .compressed_chunks()
+                .map(|(_, chunk)| chunk.memory_size())
+                .sum::<usize>()) as f64
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::terrain::{block, state::BlockState};
+
+    fn sample_chunk() -> Chunk {
+        let mut chunk = Chunk::new();
+        for (x, z) in CompressedChunk::columns() {
+            for y in 0..(x + z) {
+                chunk.data[x][y][z] = Some(block::STONE);
+            }
+            chunk.light[x][x + z][z] = (x as u8) << 4;
+        }
+        chunk.data[3][200][7] = Some(block::WATER);
+        chunk.fluid_levels[3][200][7] = 5;
+        chunk.states[3][0][7] = BlockState(2);
+        chunk
+    }
+
+    #[test]
+    fn compressed_chunks_read_like_the_original() {
+        let chunk = sample_chunk();
+        let compressed = CompressedChunk::compress(&chunk, Vec::new());
+        let decompressed = compressed.decompress();
+        for (x, z) in CompressedChunk::columns() {
+            for y in 0..CHUNK_HEIGHT {
+                assert_eq!(compressed.cell(x, y, z), chunk.cell(x, y, z));
+                assert_eq!(decompressed.cell(x, y, z), chunk.cell(x, y, z));
+            }
+        }
+        assert!(compressed.memory_size() < Chunk::MEMORY_SIZE / 10);
+    }
+
+    #[test]
+    fn compressed_chunks_stay_loaded_and_decompress_on_change() {
+        let mut world = VoxelWorld::default();
+        world.chunks.insert(IVec2::ZERO, sample_chunk());
+        world.compress(IVec2::ZERO, Vec::new());
+        assert!(!world.chunks.contains_key(&IVec2::ZERO));
+
+        let pos = IVec3::new(3, 200, 7);
+        assert!(world.is_loaded(pos));
+        assert_eq!(world.get(pos), Some(block::WATER));
+        assert_eq!(world.fluid_level(pos), 5);
+
+        world.set(pos, None);
+        assert!(world.chunks.contains_key(&IVec2::ZERO));
+        assert_eq!(world.get(pos), None);
+        assert!(world.take_dirty().contains(&IVec2::ZERO));
+    }
+}
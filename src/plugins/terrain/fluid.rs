@@ -0,0 +1,376 @@
+use bevy::prelude::*;
+
+use super::{
+    block,
+    tick::BlockTicks,
+    world::{VoxelID, VoxelWorld, CHUNK_HEIGHT, CHUNK_WIDTH},
+};
+
+/// Fluid level of a source block. Sources never drain.
+pub const SOURCE_LEVEL: u8 = 8;
+/// Fluid level of a column falling onto whatever is below it.
+pub const FALLING_LEVEL: u8 = 7;
+
+const HORIZONTAL: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+
+impl Fluid {
+    #[must_use]
+    pub const fn from_voxel(voxel: VoxelID) -> Option<Self> {
+        match voxel {
+            block::WATER => Some(Self::Water),
+            block::LAVA => Some(Self::Lava),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn voxel(self) -> VoxelID {
+        match self {
+            Self::Water => block::WATER,
+            Self::Lava => block::LAVA,
+        }
+    }
+
+    /// How many levels the fluid loses per block of horizontal spread.
+    const fn decay(self) -> u8 {
+        match self {
+            Self::Water => 1,
+            Self::Lava => 2,
+        }
+    }
+
+    /// How many ticks a cell waits before reacting to a change.
+    const fn delay(self) -> u64 {
+        match self {
+            Self::Water => 5,
+            Self::Lava => 15,
+        }
+    }
+}
+
+/// Wakes up the fluids around `pos`, e.g. after the voxel there was changed. Fluid cells
+/// are simulated as scheduled block updates, so a settled world costs nothing.
+pub fn notify(world: &VoxelWorld, ticks: &mut BlockTicks, pos: IVec3) {
+    for pos in std::iter::once(pos).chain(NEIGHBOURS.iter().map(|&dir| pos + dir)) {
+        if let Some(fluid) = world.get(pos).and_then(Fluid::from_voxel) {
+            ticks.schedule(pos, fluid.delay());
+        }
+    }
+}
+
+/// Wakes up the fluids of the chunk at `chunk_pos` that aren't settled, e.g. once it is
+/// generated. Generation places fluids without going through the world's updates.
+pub fn wake_chunk(world: &VoxelWorld, ticks: &mut BlockTicks, chunk_pos: IVec2) {
+    let origin = IVec3::new(
+        chunk_pos.x * CHUNK_WIDTH as i32,
+        0,
+        chunk_pos.y * CHUNK_WIDTH as i32,
+    );
+    for pos in (0..CHUNK_WIDTH as i32)
+        .flat_map(|x| (0..CHUNK_HEIGHT as i32).map(move |y| (x, y)))
+        .flat_map(|(x, y)| (0..CHUNK_WIDTH as i32).map(move |z| IVec3::new(x, y, z)))
+        .map(|pos| origin + pos)
+    {
+        let Some(fluid) = world.get(pos).and_then(Fluid::from_voxel) else {
+            continue;
+        };
+        let unsettled = HORIZONTAL
+            .iter()
+            .chain(std::iter::once(&IVec3::NEG_Y))
+            .any(|&dir| can_flow_into(world, pos + dir, fluid))
+            || (fluid == Fluid::Lava
+                && HORIZONTAL
+                    .iter()
+                    .chain(std::iter::once(&IVec3::Y))
+                    .any(|&dir| world.get(pos + dir) == Some(block::WATER)));
+        if unsettled {
+            ticks.schedule(pos, fluid.delay());
+        }
+    }
+}
+
+/// Runs the scheduled update of the fluid cell at `pos`.
+pub fn update(world: &mut VoxelWorld, ticks: &mut BlockTicks, pos: IVec3) {
+    let Some(fluid) = world.get(pos).and_then(Fluid::from_voxel) else {
+        return;
+    };
+    let mut level = world.fluid_level(pos);
+
+    // Lava touched by water from the side or above hardens.
+    if fluid == Fluid::Lava
+        && HORIZONTAL
+            .iter()
+            .chain(std::iter::once(&IVec3::Y))
+            .any(|&dir| world.get(pos + dir) == Some(block::WATER))
+    {
+        let hardened = if level == SOURCE_LEVEL {
+            block::OBSIDIAN
+        } else {
+            block::COBBLESTONE
+        };
+        world.set(pos, Some(hardened));
+        notify(world, ticks, pos);
+        return;
+    }
+
+    if level != SOURCE_LEVEL {
+        let expected = expected_level(world, pos, fluid);
+        if expected != level {
+            if expected == 0 {
+                world.set(pos, None);
+            } else {
+                world.set_fluid(pos, Some(fluid.voxel()), expected);
+            }
+            notify(world, ticks, pos);
+            if expected == 0 {
+                return;
+            }
+            level = expected;
+        }
+    }
+
+    let below = pos + IVec3::NEG_Y;
+    if can_flow_into(world, below, fluid) {
+        flow_into(world, ticks, below, fluid, FALLING_LEVEL);
+        return;
+    }
+    let spread = level.min(FALLING_LEVEL).saturating_sub(fluid.decay());
+    if spread == 0 {
+        return;
+    }
+    for dir in HORIZONTAL {
+        if can_flow_into(world, pos + dir, fluid) {
+            flow_into(world, ticks, pos + dir, fluid, spread);
+        }
+    }
+}
+
+/// The level a flowing (non-source) cell should have given its surroundings.
+fn expected_level(world: &VoxelWorld, pos: IVec3, fluid: Fluid) -> u8 {
+    if world.get(pos + IVec3::Y) == Some(fluid.voxel()) {
+        return FALLING_LEVEL;
+    }
+
+    let mut sources = 0;
+    let mut level = 0;
+    for dir in HORIZONTAL {
+        if world.get(pos + dir) != Some(fluid.voxel()) {
+            continue;
+        }
+        let neighbour = world.fluid_level(pos + dir);
+        if neighbour == SOURCE_LEVEL {
+            sources += 1;
+        }
+        level = level.max(neighbour.min(FALLING_LEVEL).saturating_sub(fluid.decay()));
+    }
+
+    // Water between two sources becomes a source itself, as long as it rests on something.
+    let below = pos + IVec3::NEG_Y;
+    let supported = match world.get(below) {
+        Some(voxel) if voxel == fluid.voxel() => world.fluid_level(below) == SOURCE_LEVEL,
+        Some(voxel) => Fluid::from_voxel(voxel).is_none(),
+        None => false,
+    };
+    if fluid == Fluid::Water && sources >= 2 && supported {
+        SOURCE_LEVEL
+    } else {
+        level
+    }
+}
+
+fn can_flow_into(world: &VoxelWorld, pos: IVec3, fluid: Fluid) -> bool {
+    if !world.is_loaded(pos) {
+        return false;
+    }
+    match world.get(pos).map(Fluid::from_voxel) {
+        None => true,
+        Some(Some(other)) if other == fluid => world.fluid_level(pos) != SOURCE_LEVEL,
+        Some(Some(_)) => true,
+        Some(None) => false,
+    }
+}
+
+fn flow_into(world: &mut VoxelWorld, ticks: &mut BlockTicks, pos: IVec3, fluid: Fluid, level: u8) {
+    match world.get(pos).and_then(Fluid::from_voxel) {
+        None => world.set_fluid(pos, Some(fluid.voxel()), level),
+        Some(other) if other == fluid => {
+            if world.fluid_level(pos) >= level {
+                return;
+            }
+            world.set_fluid(pos, Some(fluid.voxel()), level);
+        }
+        Some(Fluid::Water) => world.set(pos, Some(block::STONE)),
+        Some(Fluid::Lava) => {
+            let hardened = if world.fluid_level(pos) == SOURCE_LEVEL {
+                block::OBSIDIAN
+            } else {
+                block::COBBLESTONE
+            };
+            world.set(pos, Some(hardened));
+        }
+    }
+    notify(world, ticks, pos);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::terrain::world::Chunk;
+
+    /// A world of a single chunk with a stone floor at the bottom. Fluids can't leave it.
+    fn floor() -> VoxelWorld {
+        let mut world = VoxelWorld::default();
+        let mut chunk = Chunk::new();
+        for (x, z) in (0..CHUNK_WIDTH).flat_map(|x| (0..CHUNK_WIDTH).map(move |z| (x, z))) {
+            chunk.data[x][0][z] = Some(block::STONE);
+        }
+        world.chunks.insert(IVec2::ZERO, chunk);
+        world
+    }
+
+    fn run(world: &mut VoxelWorld, ticks: &mut BlockTicks, count: usize) {
+        for _ in 0..count {
+            let updates = world.take_updates();
+            ticks.step(world, &updates);
+        }
+    }
+
+    fn levels(world: &VoxelWorld, y: i32) -> Vec<(Option<VoxelID>, u8)> {
+        (0..CHUNK_WIDTH as i32)
+            .flat_map(|x| (0..CHUNK_WIDTH as i32).map(move |z| IVec3::new(x, y, z)))
+            .map(|pos| (world.get(pos), world.fluid_level(pos)))
+            .collect()
+    }
+
+    #[test]
+    fn water_spreads_one_level_per_block() {
+        let mut world = floor();
+        let mut ticks = BlockTicks::default();
+        let source = IVec3::new(4, 1, 8);
+        world.set_fluid(source, Some(block::WATER), SOURCE_LEVEL);
+        run(&mut world, &mut ticks, 200);
+
+        assert_eq!(world.fluid_level(source), SOURCE_LEVEL);
+        for distance in 1..=6 {
+            let pos = source + IVec3::X * distance;
+            assert_eq!(world.get(pos), Some(block::WATER));
+            assert_eq!(world.fluid_level(pos), FALLING_LEVEL - distance as u8);
+        }
+        assert_eq!(world.get(source + IVec3::X * 7), None);
+        // Flows along the floor, never up.
+        assert_eq!(world.get(source + IVec3::Y), None);
+    }
+
+    #[test]
+    fn lava_spreads_less_than_water() {
+        let mut world = floor();
+        let mut ticks = BlockTicks::default();
+        let source = IVec3::new(4, 1, 8);
+        world.set_fluid(source, Some(block::LAVA), SOURCE_LEVEL);
+        run(&mut world, &mut ticks, 400);
+
+        for distance in 1..=3 {
+            let pos = source + IVec3::X * distance;
+            assert_eq!(world.get(pos), Some(block::LAVA));
+            assert_eq!(world.fluid_level(pos), FALLING_LEVEL - 2 * distance as u8);
+        }
+        assert_eq!(world.get(source + IVec3::X * 4), None);
+    }
+
+    #[test]
+    fn water_falls_before_spreading() {
+        let mut world = floor();
+        let mut ticks = BlockTicks::default();
+        let source = IVec3::new(8, 5, 8);
+        world.set_fluid(source, Some(block::WATER), SOURCE_LEVEL);
+        run(&mut world, &mut ticks, 200);
+
+        for y in 1..5 {
+            let pos = IVec3::new(8, y, 8);
+            assert_eq!(world.get(pos), Some(block::WATER));
+            assert_eq!(world.fluid_level(pos), FALLING_LEVEL);
+        }
+        // Only the bottom of the column spreads.
+        assert_eq!(world.get(IVec3::new(9, 4, 8)), None);
+        assert_eq!(world.get(IVec3::new(9, 1, 8)), Some(block::WATER));
+    }
+
+    #[test]
+    fn flowing_water_drains_without_its_source() {
+        let mut world = floor();
+        let mut ticks = BlockTicks::default();
+        let source = IVec3::new(8, 1, 8);
+        world.set_fluid(source, Some(block::WATER), SOURCE_LEVEL);
+        run(&mut world, &mut ticks, 200);
+        world.set(source, None);
+        run(&mut world, &mut ticks, 400);
+
+        assert!(levels(&world, 1).iter().all(|&(voxel, _)| voxel.is_none()));
+    }
+
+    #[test]
+    fn water_between_two_sources_becomes_a_source() {
+        let mut world = floor();
+        let mut ticks = BlockTicks::default();
+        world.set_fluid(IVec3::new(4, 1, 8), Some(block::WATER), SOURCE_LEVEL);
+        world.set_fluid(IVec3::new(6, 1, 8), Some(block::WATER), SOURCE_LEVEL);
+        run(&mut world, &mut ticks, 200);
+
+        assert_eq!(world.fluid_level(IVec3::new(5, 1, 8)), SOURCE_LEVEL);
+        // Only cells between two sources turn into one.
+        assert_eq!(world.fluid_level(IVec3::new(7, 1, 8)), FALLING_LEVEL - 1);
+    }
+
+    #[test]
+    fn lava_source_touched_by_water_turns_into_obsidian() {
+        let mut world = floor();
+        let mut ticks = BlockTicks::default();
+        world.set_fluid(IVec3::new(4, 1, 8), Some(block::LAVA), SOURCE_LEVEL);
+        world.set_fluid(IVec3::new(5, 1, 8), Some(block::WATER), SOURCE_LEVEL);
+        run(&mut world, &mut ticks, 100);
+
+        assert_eq!(world.get(IVec3::new(4, 1, 8)), Some(block::OBSIDIAN));
+        assert_eq!(world.get(IVec3::new(5, 1, 8)), Some(block::WATER));
+    }
+
+    #[test]
+    fn generated_fluids_are_woken_up() {
+        let mut world = floor();
+        let mut ticks = BlockTicks::default();
+        // Written straight into the chunk, as the generator does.
+        let chunk = world.chunks.get_mut(&IVec2::ZERO).unwrap();
+        chunk.data[8][1][8] = Some(block::WATER);
+        chunk.fluid_levels[8][1][8] = SOURCE_LEVEL;
+        wake_chunk(&world, &mut ticks, IVec2::ZERO);
+        run(&mut world, &mut ticks, 50);
+
+        assert_eq!(world.get(IVec3::new(9, 1, 8)), Some(block::WATER));
+    }
+
+    #[test]
+    fn simulation_is_deterministic() {
+        let simulate = || {
+            let mut world = floor();
+            let mut ticks = BlockTicks::default();
+            world.set_fluid(IVec3::new(3, 4, 3), Some(block::WATER), SOURCE_LEVEL);
+            world.set_fluid(IVec3::new(10, 1, 10), Some(block::LAVA), SOURCE_LEVEL);
+            run(&mut world, &mut ticks, 300);
+            levels(&world, 1)
+        };
+        assert_eq!(simulate(), simulate());
+    }
+}
@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    block, fluid,
+    state::BlockState,
+    tick::BlockTicks,
+    world::{VoxelID, VoxelWorld},
+};
+
+/// Ticks between a gravity block losing its support and starting to fall.
+const DELAY: u64 = 2;
+/// Speed under which a falling block counts as resting.
+const REST_SPEED: f32 = 0.1;
+/// Seconds a falling block must rest before it is put back into the world.
+const REST_TIME: f32 = 0.25;
+/// Falling blocks this far below the world are dropped.
+const MIN_HEIGHT: f32 = -64.0;
+/// How far from where it lands a falling block looks for a free cell.
+const SEARCH_RADIUS: i32 = 2;
+
+/// A gravity block taken out of the world while it falls.
+#[derive(Component)]
+pub struct FallingBlock {
+    pub voxel: VoxelID,
+    pub state: BlockState,
+    rest_time: f32,
+}
+
+/// Whether the voxel at `pos` can hold a block, i.e. it is loaded and only holds air or fluid.
+fn is_free(world: &VoxelWorld, pos: IVec3) -> bool {
+    world.is_loaded(pos)
+        && world
+            .get(pos)
+            .is_none_or(|voxel| fluid::Fluid::from_voxel(voxel).is_some())
+}
+
+fn has_gravity(world: &VoxelWorld, pos: IVec3) -> bool {
+    world
+        .get(pos)
+        .is_some_and(|voxel| block::def(voxel).gravity)
+}
+
+/// Schedules the gravity blocks that may have lost their support after `pos` changed.
+pub fn notify(world: &VoxelWorld, ticks: &mut BlockTicks, pos: IVec3) {
+    for pos in [pos, pos + IVec3::Y] {
+        if has_gravity(world, pos) {
+            ticks.schedule(pos, DELAY);
+        }
+    }
+}
+
+/// Runs the scheduled update of the gravity block at `pos`, taking it out of the world if
+/// nothing is under it.
+pub fn update(world: &mut VoxelWorld, ticks: &mut BlockTicks, pos: IVec3) {
+    let Some(voxel) = world.get(pos) else {
+        return;
+    };
+    if block::def(voxel).gravity && is_free(world, pos + IVec3::NEG_Y) {
+        let state = world.state(pos);
+        world.set(pos, None);
+        ticks.fall(pos, voxel, state);
+    }
+}
+
+/// Spawns a rigid body for every block that started falling during the last ticks.
+pub fn spawn_system(
+    mut commands: Commands,
+    mut ticks: ResMut<BlockTicks>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    res: Res<crate::plugins::resources::GameResources>,
+    mut handles: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+) {
+    let falling = ticks.take_falling();
+    if falling.is_empty() {
+        return;
+    }
+    let (mesh, material) = handles
+        .get_or_insert_with(|| {
+            (
+                meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+                materials.add(StandardMaterial {
+                    base_color_texture: Some(res.block_textures.clone()),
+                    perceptual_roughness: 1.0,
+                    ..default()
+                }),
+            )
+        })
+        .clone();
+
+    for (pos, voxel, state) in falling {
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(pos.as_vec3()),
+                ..default()
+            },
+            RigidBody::Dynamic,
+            // Slightly smaller than a voxel, so it doesn't snag on the walls of a shaft.
+            Collider::cuboid(0.49, 0.49, 0.49),
+            LockedAxes::ROTATION_LOCKED,
+            Velocity::zero(),
+            FallingBlock {
+                voxel,
+                state,
+                rest_time: 0.0,
+            },
+            Name::new("Falling Block"),
+        ));
+    }
+}
+
+/// Puts falling blocks that came to rest back into the world, at the free cell nearest to
+/// where they landed.
+pub fn settle_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut world: ResMut<VoxelWorld>,
+    mut blocks: Query<(Entity, &Transform, &Velocity, &mut FallingBlock)>,
+) {
+    for (entity, transform, velocity, mut block) in &mut blocks {
+        if transform.translation.y < MIN_HEIGHT {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        if velocity.linvel.length() > REST_SPEED {
+            block.rest_time = 0.0;
+            continue;
+        }
+        block.rest_time += time.delta_seconds();
+        if block.rest_time < REST_TIME {
+            continue;
+        }
+
+        let landed = transform.translation.round().as_ivec3();
+        let range = -SEARCH_RADIUS..=SEARCH_RADIUS;
+        let nearest = range
+            .clone()
+            .flat_map(|x| range.clone().map(move |y| (x, y)))
+            .flat_map(|(x, y)| range.clone().map(move |z| IVec3::new(x, y, z)))
+            .filter(|&offset| is_free(&world, landed + offset))
+            // Prefer cells above over cells below at the same distance.
+            .min_by_key(|&offset| (offset.dot(offset), -offset.y, offset.x, offset.z));
+        if let Some(offset) = nearest {
+            world.set(landed + offset, Some(block.voxel));
+            world.set_state(landed + offset, block.state);
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
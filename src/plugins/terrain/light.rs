@@ -0,0 +1,302 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use super::{
+    block,
+    world::{VoxelWorld, CHUNK_HEIGHT, CHUNK_WIDTH},
+};
+
+pub const MAX_LIGHT: u8 = 15;
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Sky,
+    Block,
+}
+
+impl Channel {
+    fn get(self, world: &VoxelWorld, pos: IVec3) -> u8 {
+        let light = world.light(pos);
+        match self {
+            Self::Sky => light >> 4,
+            Self::Block => light & 0xF,
+        }
+    }
+
+    fn set(self, world: &mut VoxelWorld, pos: IVec3, value: u8) {
+        let light = world.light(pos);
+        world.set_light(
+            pos,
+            match self {
+                Self::Sky => (value << 4) | (light & 0xF),
+                Self::Block => (light & 0xF0) | value,
+            },
+        );
+    }
+
+    /// The light `pos + dir` receives from a neighbour at `level`.
+    /// Full sky light travels straight down without fading.
+    fn spread(self, level: u8, dir: IVec3) -> u8 {
+        if self == Self::Sky && dir == IVec3::NEG_Y && level == MAX_LIGHT {
+            MAX_LIGHT
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+}
+
+/// Brightness multiplier for a packed light value, as baked into vertex colors.
+#[must_use]
+pub fn brightness(light: u8) -> f32 {
+    let level = (light >> 4).max(light & 0xF);
+    0.8f32.powi(i32::from(MAX_LIGHT - level))
+}
+
+/// Computes the light of a freshly inserted chunk, and lets light flow in from and out to
+/// the chunks around it.
+pub fn light_chunk(world: &mut VoxelWorld, chunk_pos: IVec2) {
+    let origin = IVec3::new(
+        chunk_pos.x * CHUNK_WIDTH as i32,
+        0,
+        chunk_pos.y * CHUNK_WIDTH as i32,
+    );
+    let mut sky = VecDeque::new();
+    let mut blocks = VecDeque::new();
+
+    // Heights of the lowest voxel of each column that still sees the sky.
+    let mut heights = [[0; CHUNK_WIDTH]; CHUNK_WIDTH];
+    for (x, z) in (0..CHUNK_WIDTH).flat_map(|x| (0..CHUNK_WIDTH).map(move |z| (x, z))) {
+        let column = origin + IVec3::new(x as i32, 0, z as i32);
+        let mut y = CHUNK_HEIGHT as i32;
+        while y > 0 && block::is_transparent(world.get(column + IVec3::Y * (y - 1))) {
+            y -= 1;
+            Channel::Sky.set(world, column + IVec3::Y * y, MAX_LIGHT);
+        }
+        heights[x][z] = y;
+
+        for y in 0..CHUNK_HEIGHT as i32 {
+            let pos = column + IVec3::Y * y;
+            let emission = world.get(pos).map_or(0, |voxel| block::def(voxel).emission);
+            if emission > 0 {
+                Channel::Block.set(world, pos, emission);
+                blocks.push_back(pos);
+            }
+        }
+    }
+
+    // Only sky-lit voxels next to a taller column can light anything sideways. The columns
+    // of the neighbouring chunks are not known here, so the edges are always flooded.
+    for (x, z) in (0..CHUNK_WIDTH).flat_map(|x| (0..CHUNK_WIDTH).map(move |z| (x, z))) {
+        let tallest_neighbour = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .iter()
+            .map(|&(dx, dz)| {
+                let (nx, nz) = (x as i32 + dx, z as i32 + dz);
+                if (0..CHUNK_WIDTH as i32).contains(&nx) && (0..CHUNK_WIDTH as i32).contains(&nz) {
+                    heights[nx as usize][nz as usize]
+                } else {
+                    CHUNK_HEIGHT as i32
+                }
+            })
+            .max()
+            .unwrap_or_default();
+        let column = origin + IVec3::new(x as i32, 0, z as i32);
+        sky.extend((heights[x][z]..tallest_neighbour).map(|y| column + IVec3::Y * y));
+    }
+
+    // Pull in the light already present on the edges of the neighbouring chunks.
+    for i in 0..CHUNK_WIDTH as i32 {
+        for (edge, outside) in [
+            (IVec3::new(0, 0, i), IVec3::NEG_X),
+            (IVec3::new(CHUNK_WIDTH as i32 - 1, 0, i), IVec3::X),
+            (IVec3::new(i, 0, 0), IVec3::NEG_Z),
+            (IVec3::new(i, 0, CHUNK_WIDTH as i32 - 1), IVec3::Z),
+        ] {
+            for y in 0..CHUNK_HEIGHT as i32 {
+                let pos = origin + edge + outside + IVec3::Y * y;
+                if Channel::Sky.get(world, pos) > 1 {
+                    sky.push_back(pos);
+                }
+                if Channel::Block.get(world, pos) > 1 {
+                    blocks.push_back(pos);
+                }
+            }
+        }
+    }
+
+    propagate(world, Channel::Sky, sky);
+    propagate(world, Channel::Block, blocks);
+}
+
+/// Updates the light around `pos` after the voxel there was replaced.
+pub fn update(world: &mut VoxelWorld, pos: IVec3) {
+    let voxel = world.get(pos);
+    for channel in [Channel::Sky, Channel::Block] {
+        remove(world, channel, pos);
+
+        let mut queue = VecDeque::new();
+        if channel == Channel::Block {
+            let emission = voxel.map_or(0, |voxel| block::def(voxel).emission);
+            if emission > 0 {
+                channel.set(world, pos, emission);
+                queue.push_back(pos);
+            }
+        }
+        if block::is_transparent(voxel) {
+            queue.extend(
+                NEIGHBOURS
+                    .iter()
+                    .map(|&dir| pos + dir)
+                    .filter(|&neighbour| channel.get(world, neighbour) > 0),
+            );
+        }
+        propagate(world, channel, queue);
+    }
+}
+
+/// Flood-fills light outwards from every position in `queue`.
+fn propagate(world: &mut VoxelWorld, channel: Channel, mut queue: VecDeque<IVec3>) {
+    while let Some(pos) = queue.pop_front() {
+        let level = channel.get(world, pos);
+        for dir in NEIGHBOURS {
+            let next = pos + dir;
+            if !world.is_loaded(next) || !block::is_transparent(world.get(next)) {
+                continue;
+            }
+            let spread = channel.spread(level, dir);
+            if spread > channel.get(world, next) {
+                channel.set(world, next, spread);
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+/// Clears the light at `pos` and everything that was lit through it, then refills the
+/// cleared area from the light sources bordering it.
+fn remove(world: &mut VoxelWorld, channel: Channel, pos: IVec3) {
+    let level = channel.get(world, pos);
+    if level == 0 {
+        return;
+    }
+    channel.set(world, pos, 0);
+
+    let mut removal = VecDeque::from([(pos, level)]);
+    let mut refill = VecDeque::new();
+    while let Some((pos, level)) = removal.pop_front() {
+        for dir in NEIGHBOURS {
+            let next = pos + dir;
+            let next_level = channel.get(world, next);
+            if next_level == 0 || !world.is_loaded(next) {
+                continue;
+            }
+            if next_level < level || channel.spread(level, dir) == next_level {
+                channel.set(world, next, 0);
+                removal.push_back((next, next_level));
+            } else {
+                refill.push_back(next);
+            }
+        }
+    }
+    propagate(world, channel, refill);
+}
+
+pub fn light_system(mut world: ResMut<VoxelWorld>) {
+    for pos in world.take_changed() {
+        update(&mut world, pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::terrain::world::Chunk;
+
+    /// A chunk with a stone floor at the bottom, and a stone roof at `roof` if any.
+    fn chunk(roof: Option<usize>) -> Chunk {
+        let mut chunk = Chunk::new();
+        for (x, z) in (0..CHUNK_WIDTH).flat_map(|x| (0..CHUNK_WIDTH).map(move |z| (x, z))) {
+            chunk.data[x][0][z] = Some(block::STONE);
+            if let Some(y) = roof {
+                chunk.data[x][y][z] = Some(block::STONE);
+            }
+        }
+        chunk
+    }
+
+    /// A world made of `chunk` alone, with its light computed.
+    fn lit(chunk: Chunk) -> VoxelWorld {
+        let mut world = VoxelWorld::default();
+        world.chunks.insert(IVec2::ZERO, chunk);
+        light_chunk(&mut world, IVec2::ZERO);
+        world
+    }
+
+    #[test]
+    fn sky_light_falls_straight_down() {
+        let mut chunk = chunk(None);
+        chunk.data[8][10][8] = Some(block::STONE);
+        let world = lit(chunk);
+
+        for y in 1..CHUNK_HEIGHT as i32 {
+            assert_eq!(Channel::Sky.get(&world, IVec3::new(4, y, 4)), MAX_LIGHT);
+        }
+        assert_eq!(Channel::Sky.get(&world, IVec3::new(4, 0, 4)), 0);
+        // Under the block, the light only comes in from the side.
+        for y in 1..10 {
+            assert_eq!(Channel::Sky.get(&world, IVec3::new(8, y, 8)), MAX_LIGHT - 1);
+        }
+    }
+
+    #[test]
+    fn block_light_fades_one_level_per_block() {
+        let mut chunk = chunk(Some(20));
+        chunk.data[1][1][8] = Some(block::TORCH);
+        let world = lit(chunk);
+        let emission = block::def(block::TORCH).emission;
+
+        for distance in 0..emission {
+            let pos = IVec3::new(1 + i32::from(distance), 1, 8);
+            assert_eq!(Channel::Block.get(&world, pos), emission - distance);
+            assert_eq!(Channel::Sky.get(&world, pos), 0);
+        }
+    }
+
+    #[test]
+    fn placing_a_block_shades_the_column_below() {
+        let mut world = lit(chunk(None));
+        let roof = IVec3::new(8, 10, 8);
+        world.set(roof, Some(block::STONE));
+        update(&mut world, roof);
+
+        assert_eq!(Channel::Sky.get(&world, roof), 0);
+        for y in 1..10 {
+            assert_eq!(Channel::Sky.get(&world, IVec3::new(8, y, 8)), MAX_LIGHT - 1);
+        }
+    }
+
+    #[test]
+    fn replacing_a_torch_removes_its_light() {
+        let mut chunk = chunk(Some(20));
+        chunk.data[1][1][8] = Some(block::TORCH);
+        let mut world = lit(chunk);
+        let torch = IVec3::new(1, 1, 8);
+        world.set(torch, Some(block::STONE));
+        update(&mut world, torch);
+
+        for (x, y) in (0..CHUNK_WIDTH as i32).flat_map(|x| (1..20).map(move |y| (x, y))) {
+            for z in 0..CHUNK_WIDTH as i32 {
+                assert_eq!(Channel::Block.get(&world, IVec3::new(x, y, z)), 0);
+            }
+        }
+    }
+}
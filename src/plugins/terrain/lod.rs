@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+use super::{
+    world::{VoxelWorld, CHUNK_WIDTH},
+    ChunkLayers, ChunkMesh,
+};
+use crate::plugins::player::PlayerController;
+
+/// Distance bands, in blocks from the player, past which chunks switch to coarser meshes.
+#[derive(Resource)]
+pub struct LodSettings {
+    /// The `n`th band is where LOD `n + 1`, merging `2^(n + 1)` voxels per side, starts.
+    /// Must be sorted in ascending order.
+    pub bands: Vec<f32>,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            bands: vec![48.0, 96.0, 160.0],
+        }
+    }
+}
+
+impl LodSettings {
+    #[must_use]
+    pub fn level(&self, distance: f32) -> u32 {
+        self.bands
+            .iter()
+            .take_while(|&&band| distance >= band)
+            .count() as u32
+    }
+}
+
+/// The level of detail the mesh of a chunk is currently built at.
+#[derive(Component, Default)]
+pub struct ChunkLod(pub u32);
+
+pub fn lod_system(
+    settings: Res<LodSettings>,
+    world: Res<VoxelWorld>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    players: Query<&GlobalTransform, With<PlayerController>>,
+    mut chunks: Query<(&ChunkMesh, &mut ChunkLod, &ChunkLayers)>,
+) {
+    // Detached cameras don't count, the detail follows the player.
+    let Ok(player) = players.get_single() else {
+        return;
+    };
+    let player = player.translation();
+
+    for (chunk_mesh, mut lod, layers) in &mut chunks {
+        let center = (chunk_mesh.0.as_vec2() + 0.5) * CHUNK_WIDTH as f32 - 0.5;
+        let level = settings.level(center.distance(Vec2::new(player.x, player.z)));
+        if level == lod.0 {
+            continue;
+        }
+
+        if let Some(chunk) = world.chunk(chunk_mesh.0) {
+            layers.replace(
+                &mut meshes,
+                chunk.create_lod_mesh(&world, chunk_mesh.0, level),
+            );
+            lod.0 = level;
+        }
+    }
+}
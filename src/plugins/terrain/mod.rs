@@ -1,53 +1,244 @@
+use std::time::Duration;
+
 use bevy::{prelude::*, render::view::NoFrustumCulling};
 use bevy_rapier3d::prelude::*;
 use iyes_loopless::prelude::*;
 use noise::OpenSimplex;
 
+pub mod animation;
+pub mod block;
+pub mod block_entity;
+pub mod cache;
+pub mod fluid;
+pub mod gravity;
+pub mod light;
+pub mod lod;
+pub mod shape;
+pub mod spawn;
+pub mod state;
+pub mod support;
+pub mod texture;
+pub mod tick;
 pub mod world;
 
+/// Simulation ticks per second, independent of the frame rate.
+pub const TICK_RATE: f64 = 20.0;
+const TICK: &str = "terrain_tick";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum TerrainSystem {
+    /// Generates the starting terrain and finds the spawn point.
+    Setup,
+    Light,
+}
+
 pub struct TerrainPlugin;
 
 impl Plugin for TerrainPlugin {
     fn build(&self, app: &mut App) {
-        app.add_enter_system(crate::AppState::InGame, terrain_setup);
+        app.init_resource::<world::VoxelWorld>()
+            .init_resource::<tick::BlockTicks>()
+            .init_resource::<support::StructuralSupport>()
+            .add_event::<support::CaveInEvent>()
+            .add_event::<world::VoxelEditEvent>()
+            .init_resource::<lod::LodSettings>()
+            .init_resource::<cache::ChunkCache>()
+            .init_resource::<animation::TextureAnimations>()
+            .register_type::<block_entity::BlockEntity>()
+            .register_type::<block_entity::BlockEntityData>()
+            .add_startup_system(cache::setup_diagnostic)
+            .add_fixed_timestep(Duration::from_secs_f64(1.0 / TICK_RATE), TICK)
+            .add_fixed_timestep_system(
+                TICK,
+                0,
+                tick::tick_system.run_in_state(crate::AppState::InGame),
+            )
+            .add_fixed_timestep_system(
+                TICK,
+                0,
+                block_entity::tick_system.run_in_state(crate::AppState::InGame),
+            )
+            .add_enter_system(
+                crate::AppState::InGame,
+                terrain_setup.label(TerrainSystem::Setup),
+            )
+            .add_system(
+                light::light_system
+                    .run_in_state(crate::AppState::InGame)
+                    .label(TerrainSystem::Light),
+            )
+            .add_system(
+                remesh_system
+                    .run_in_state(crate::AppState::InGame)
+                    .after(TerrainSystem::Light),
+            )
+            .add_system(lod::lod_system.run_in_state(crate::AppState::InGame))
+            .add_system(cache::cache_system.run_in_state(crate::AppState::InGame))
+            .add_system(animation::animation_system.run_in_state(crate::AppState::InGame))
+            .add_system(block_entity::block_entity_system.run_in_state(crate::AppState::InGame))
+            .add_system(gravity::spawn_system.run_in_state(crate::AppState::InGame))
+            .add_system(gravity::settle_system.run_in_state(crate::AppState::InGame))
+            .add_system(edit_event_system.run_in_state(crate::AppState::InGame));
+    }
+}
+
+/// Marks the entity holding the meshes of the chunk at the given chunk position.
+#[derive(Component)]
+pub struct ChunkMesh(pub IVec2);
+
+/// The meshes of a chunk, indexed by [`block::mesh_slot`]. Each one is drawn by a child
+/// entity of the chunk with the matching material.
+#[derive(Component)]
+pub struct ChunkLayers(pub Vec<Handle<Mesh>>);
+
+impl ChunkLayers {
+    /// Replaces the contents of every layer in place, keeping the handles.
+    pub fn replace(&self, meshes: &mut Assets<Mesh>, new_meshes: Vec<Mesh>) {
+        for (handle, new_mesh) in self.0.iter().zip(new_meshes) {
+            if let Some(mesh) = meshes.get_mut(handle) {
+                *mesh = new_mesh;
+            }
+        }
+    }
+}
+
+/// The material drawing the given layer of every chunk. Chunks carry their light in their
+/// vertex colors, so the material ignores the lights of the scene.
+fn layer_material(layer: block::RenderLayer, texture: Handle<Image>) -> StandardMaterial {
+    let (base_color, alpha_mode) = match layer {
+        block::RenderLayer::Opaque => (Color::GREEN, AlphaMode::Opaque),
+        block::RenderLayer::Cutout => (Color::GREEN, AlphaMode::Mask(0.5)),
+        block::RenderLayer::Translucent => (Color::rgba(1.0, 1.0, 1.0, 0.7), AlphaMode::Blend),
+    };
+    StandardMaterial {
+        base_color,
+        base_color_texture: Some(texture),
+        alpha_mode,
+        metallic: 0.0,
+        perceptual_roughness: 1.0,
+        unlit: true,
+        ..default()
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn terrain_setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    mut voxels: ResMut<world::VoxelWorld>,
+    mut animations: ResMut<animation::TextureAnimations>,
+    mut ticks: ResMut<tick::BlockTicks>,
     res: Res<super::resources::GameResources>,
 ) {
-    let simplex = OpenSimplex::new(rand::random());
-
-    for x in 0..10 {
-        for z in 0..10 {
-            let mut chunk = world::Chunk::new();
-            chunk.generate(&simplex, (x * world::CHUNK_WIDTH, z * world::CHUNK_WIDTH));
-            let mesh = chunk.create_mesh();
-
-            commands.spawn((
-                Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh).unwrap(),
-                PbrBundle {
-                    mesh: meshes.add(mesh),
-                    material: materials.add(StandardMaterial {
-                        base_color: Color::GREEN,
-                        base_color_texture: Some(res.block_textures.clone()),
-                        alpha_mode: AlphaMode::Opaque,
-                        metallic: 0.0,
-                        perceptual_roughness: 1.0,
+    // One material per mesh slot: the shared texture for each layer, then every animation.
+    let mut slot_materials = block::RenderLayer::ALL
+        .iter()
+        .map(|&layer| materials.add(layer_material(layer, res.block_textures.clone())))
+        .collect::<Vec<_>>();
+    for ((voxel, &animation), strip) in block::animated().zip(&res.animated_textures) {
+        let texture = animations.insert(&mut images, strip.clone(), animation);
+        let mut material = layer_material(block::def(voxel).layer, texture);
+        // Animated strips carry their own colors, only the layer's alpha is kept.
+        material.base_color = Color::rgba(1.0, 1.0, 1.0, material.base_color.a());
+        slot_materials.push(materials.add(material));
+    }
+    let seed = rand::random();
+    let simplex = OpenSimplex::new(seed);
+    ticks.reseed(u64::from(seed));
+    let positions = (-5..5)
+        .flat_map(|x| (-5..5).map(move |z| IVec2::new(x, z)))
+        .collect::<Vec<_>>();
+
+    for &pos in &positions {
+        let mut chunk = world::Chunk::new();
+        chunk.generate(&simplex, pos * world::CHUNK_WIDTH as i32);
+        voxels.chunks.insert(pos, chunk);
+        light::light_chunk(&mut voxels, pos);
+    }
+    // Every chunk is meshed below, after all of the light has settled.
+    voxels.take_dirty();
+    for &pos in &positions {
+        fluid::wake_chunk(&voxels, &mut ticks, pos);
+    }
+    if voxels.spawn.is_none() {
+        voxels.spawn = spawn::find(&voxels, spawn::WORLD_SPAWN, spawn::SEARCH_RADIUS);
+    }
+
+    for &pos in &positions {
+        let chunk = &voxels.chunks[&pos];
+        let layers = chunk
+            .create_mesh(&voxels, pos)
+            .into_iter()
+            .map(|mesh| meshes.add(mesh))
+            .collect::<Vec<_>>();
+        let collider = chunk.create_collider();
+
+        let mut entity = commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(Vec3::new(
+                pos.x as f32 * world::CHUNK_WIDTH as f32,
+                0.0,
+                pos.y as f32 * world::CHUNK_WIDTH as f32,
+            ))),
+            VisibilityBundle::default(),
+            ChunkMesh(pos),
+            ChunkLayers(layers.clone()),
+            lod::ChunkLod::default(),
+        ));
+        entity.with_children(|parent| {
+            for (mesh, material) in layers.into_iter().zip(slot_materials.iter().cloned()) {
+                parent.spawn((
+                    PbrBundle {
+                        mesh,
+                        material,
                         ..default()
-                    }),
-                    transform: Transform::from_translation(Vec3::new(
-                        -(5.0 * world::CHUNK_WIDTH as f32) + x as f32 * world::CHUNK_WIDTH as f32,
-                        0.0,
-                        -(5.0 * world::CHUNK_WIDTH as f32) + z as f32 * world::CHUNK_WIDTH as f32,
-                    )),
-                    ..default()
-                },
-                NoFrustumCulling,
-            ));
+                    },
+                    NoFrustumCulling,
+                ));
+            }
+        });
+        if let Some(collider) = collider {
+            entity.insert(collider);
+        }
+    }
+}
+
+/// Rebuilds the mesh and collider of every chunk modified since the last frame, once per chunk,
+/// compressed or not. Colliders always match the full detail blocks.
+fn remesh_system(
+    mut commands: Commands,
+    mut voxels: ResMut<world::VoxelWorld>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    chunks: Query<(Entity, &ChunkMesh, &lod::ChunkLod, &ChunkLayers)>,
+) {
+    let dirty = voxels.take_dirty();
+    if dirty.is_empty() {
+        return;
+    }
+
+    for (entity, chunk_mesh, lod, layers) in &chunks {
+        if !dirty.contains(&chunk_mesh.0) {
+            continue;
         }
+        let Some(chunk) = voxels.chunk(chunk_mesh.0) else {
+            continue;
+        };
+        match chunk.create_collider() {
+            Some(collider) => commands.entity(entity).insert(collider),
+            None => commands.entity(entity).remove::<Collider>(),
+        };
+        layers.replace(
+            &mut meshes,
+            chunk.create_lod_mesh(&voxels, chunk_mesh.0, lod.0),
+        );
     }
 }
+
+/// Sends the edits applied to the world since the last frame.
+fn edit_event_system(
+    mut voxels: ResMut<world::VoxelWorld>,
+    mut edits: EventWriter<world::VoxelEditEvent>,
+) {
+    edits.send_batch(voxels.take_edits());
+}
@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+/// The model of a block, before its [`super::state::BlockState`] transform is applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Cube,
+    /// Bottom half of a voxel.
+    Slab,
+    /// A slab with a step on its north half.
+    Stairs,
+    /// A post, connected to the neighbouring fences and full blocks.
+    Fence,
+    /// A thin panel on the north edge of the voxel.
+    Door,
+    Torch,
+    /// A board standing on a post.
+    Sign,
+    /// Two crossed diagonal quads, for plants.
+    Cross,
+}
+
+impl Shape {
+    /// Whether the shape fills its whole voxel, hiding the faces of its neighbours.
+    #[must_use]
+    pub const fn is_full(self) -> bool {
+        matches!(self, Self::Cube)
+    }
+
+    /// Axis-aligned boxes making up the model, as `(min, max)` corners in voxel space
+    /// centered on the origin. `connections` tells whether a fence connects to its
+    /// +X, -X, +Z and -Z neighbours.
+    #[must_use]
+    pub fn boxes(self, connections: [bool; 4]) -> Vec<(Vec3, Vec3)> {
+        const POST: f32 = 2.0 / 16.0;
+        const RAIL: f32 = 1.0 / 16.0;
+        const TORCH: f32 = 1.0 / 16.0;
+        const BOARD: f32 = 1.0 / 16.0;
+
+        match self {
+            Self::Cube => vec![(Vec3::splat(-0.5), Vec3::splat(0.5))],
+            Self::Slab => vec![(Vec3::splat(-0.5), Vec3::new(0.5, 0.0, 0.5))],
+            Self::Stairs => vec![
+                (Vec3::splat(-0.5), Vec3::new(0.5, 0.0, 0.5)),
+                (Vec3::new(-0.5, 0.0, -0.5), Vec3::new(0.5, 0.5, 0.0)),
+            ],
+            Self::Fence => {
+                let mut boxes = vec![(Vec3::new(-POST, -0.5, -POST), Vec3::new(POST, 0.5, POST))];
+                let rails = [
+                    (Vec3::new(POST, 0.0, -RAIL), Vec3::new(0.5, 0.375, RAIL)),
+                    (Vec3::new(-0.5, 0.0, -RAIL), Vec3::new(-POST, 0.375, RAIL)),
+                    (Vec3::new(-RAIL, 0.0, POST), Vec3::new(RAIL, 0.375, 0.5)),
+                    (Vec3::new(-RAIL, 0.0, -0.5), Vec3::new(RAIL, 0.375, -POST)),
+                ];
+                boxes.extend(
+                    rails
+                        .iter()
+                        .zip(connections)
+                        .filter(|&(_, connected)| connected)
+                        .map(|(&rail, _)| rail),
+                );
+                boxes
+            }
+            Self::Door => vec![(Vec3::splat(-0.5), Vec3::new(0.5, 0.5, -0.3125))],
+            Self::Torch => vec![(
+                Vec3::new(-TORCH, -0.5, -TORCH),
+                Vec3::new(TORCH, 0.125, TORCH),
+            )],
+            Self::Sign => vec![
+                (
+                    Vec3::new(-BOARD, -0.5, -BOARD),
+                    Vec3::new(BOARD, 0.0, BOARD),
+                ),
+                (Vec3::new(-0.5, 0.0, -BOARD), Vec3::new(0.5, 0.5, BOARD)),
+            ],
+            Self::Cross => Vec::new(),
+        }
+    }
+}
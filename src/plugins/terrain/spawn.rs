@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+
+use super::{
+    block,
+    fluid::Fluid,
+    world::{VoxelWorld, CHUNK_HEIGHT},
+};
+
+/// Column the spawn point is searched around, as `(x, z)`.
+pub const WORLD_SPAWN: IVec2 = IVec2::ZERO;
+/// How far from where it starts, in blocks, the search for a spawn point goes.
+pub const SEARCH_RADIUS: i32 = 32;
+
+/// Whether an entity can stand in the voxel at `pos`: it is loaded and holds neither a solid
+/// block nor a fluid.
+fn is_free(world: &VoxelWorld, pos: IVec3) -> bool {
+    world.is_loaded(pos)
+        && world
+            .get(pos)
+            .is_none_or(|voxel| !block::def(voxel).solid && Fluid::from_voxel(voxel).is_none())
+}
+
+/// Whether an entity can stand on the voxel at `pos`.
+fn is_floor(world: &VoxelWorld, pos: IVec3) -> bool {
+    world.get(pos).is_some_and(|voxel| {
+        let def = block::def(voxel);
+        def.solid && def.shape.is_full() && !def.gravity
+    })
+}
+
+/// Whether `pos` is a safe place to spawn: a floor right below, and two free blocks for the
+/// body.
+#[must_use]
+pub fn is_safe(world: &VoxelWorld, pos: IVec3) -> bool {
+    is_floor(world, pos + IVec3::NEG_Y) && is_free(world, pos) && is_free(world, pos + IVec3::Y)
+}
+
+/// Finds the safe place to spawn nearest to the column `origin`, within `radius` blocks.
+/// Columns are searched in rings of growing distance, and only their topmost block is
+/// considered so that players spawn on the surface rather than in caves. Returns the
+/// position of the lower of the two free blocks.
+#[must_use]
+pub fn find(world: &VoxelWorld, origin: IVec2, radius: i32) -> Option<IVec3> {
+    (0..=radius)
+        .flat_map(|ring| {
+            (-ring..=ring)
+                .flat_map(move |x| (-ring..=ring).map(move |z| IVec2::new(x, z)))
+                .filter(move |offset| offset.abs().max_element() == ring)
+        })
+        .find_map(|offset| {
+            let pos = surface(world, origin + offset);
+            is_safe(world, pos).then_some(pos)
+        })
+}
+
+/// The free voxel right above the topmost block of the column `column`, or above the top of
+/// the world if the column is empty or not loaded, so that nothing is ever in the way. Where
+/// players end up when there is no safe place around.
+#[must_use]
+pub fn surface(world: &VoxelWorld, column: IVec2) -> IVec3 {
+    let y = (0..CHUNK_HEIGHT as i32)
+        .rev()
+        .find(|&y| !is_free(world, IVec3::new(column.x, y, column.y)))
+        .map_or(CHUNK_HEIGHT as i32, |top| top + 1);
+    IVec3::new(column.x, y, column.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::terrain::world::{Chunk, VoxelID, CHUNK_WIDTH};
+
+    /// A world of a single chunk filled with stone up to `y = 4`, with `voxel` at `blocks`.
+    fn ground(voxel: VoxelID, blocks: &[IVec3]) -> VoxelWorld {
+        let mut chunk = Chunk::new();
+        for (x, z) in (0..CHUNK_WIDTH).flat_map(|x| (0..CHUNK_WIDTH).map(move |z| (x, z))) {
+            for y in 0..=4 {
+                chunk.data[x][y][z] = Some(block::STONE);
+            }
+        }
+        for pos in blocks {
+            chunk.data[pos.x as usize][pos.y as usize][pos.z as usize] = Some(voxel);
+        }
+        let mut world = VoxelWorld::default();
+        world.chunks.insert(IVec2::ZERO, chunk);
+        world
+    }
+
+    #[test]
+    fn safe_places_have_a_floor_and_room_for_the_body() {
+        let pos = IVec3::new(8, 5, 8);
+        assert!(is_safe(&ground(block::STONE, &[]), pos));
+        // Inside the ground, or in the air above the floor.
+        assert!(!is_safe(&ground(block::STONE, &[]), pos - IVec3::Y));
+        assert!(!is_safe(&ground(block::STONE, &[]), pos + IVec3::Y));
+        // The head would be stuck in a block.
+        assert!(!is_safe(&ground(block::STONE, &[pos + IVec3::Y]), pos));
+        // In water, on sand that would give way, and on the edge of the loaded world.
+        assert!(!is_safe(&ground(block::WATER, &[pos]), pos));
+        assert!(!is_safe(&ground(block::SAND, &[pos - IVec3::Y]), pos));
+        assert!(!is_safe(&ground(block::STONE, &[]), IVec3::new(-1, 5, 8)));
+    }
+
+    #[test]
+    fn the_nearest_safe_column_is_found() {
+        let origin = IVec2::new(8, 8);
+        let world = ground(block::WATER, &[IVec3::new(8, 5, 8)]);
+        assert_eq!(find(&world, origin, 0), None);
+        assert_eq!(find(&world, origin, 4), Some(IVec3::new(7, 5, 7)));
+        assert_eq!(
+            find(&ground(block::STONE, &[]), origin, 4),
+            Some(IVec3::new(8, 5, 8))
+        );
+    }
+
+    #[test]
+    fn the_surface_is_above_the_topmost_block() {
+        let world = ground(block::STONE, &[IVec3::new(3, 9, 3)]);
+        assert_eq!(surface(&world, IVec2::new(8, 8)), IVec3::new(8, 5, 8));
+        assert_eq!(surface(&world, IVec2::new(3, 3)), IVec3::new(3, 10, 3));
+
+        // Nothing to stand on, or no chunk at all: above the top of the world.
+        let top = CHUNK_HEIGHT as i32;
+        let mut empty = VoxelWorld::default();
+        empty.chunks.insert(IVec2::ZERO, Chunk::new());
+        assert_eq!(surface(&empty, IVec2::new(8, 8)), IVec3::new(8, top, 8));
+        assert_eq!(surface(&empty, IVec2::new(-8, 8)), IVec3::new(-8, top, 8));
+    }
+}
@@ -0,0 +1,173 @@
+use bevy::{math::Affine3A, prelude::*};
+
+/// Which properties a block keeps in its [`BlockState`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateKind {
+    None,
+    /// Oriented along an axis, like logs.
+    Axis,
+    /// Facing one of the horizontal directions, like furnaces.
+    Facing,
+    /// Facing a horizontal direction and either open or closed.
+    Door,
+    /// Growing through [`BlockState::MAX_GROWTH`] + 1 stages.
+    Crop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    Y,
+    X,
+    Z,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Facing {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Axis {
+    /// The axis `dir` is closest to.
+    #[must_use]
+    pub fn nearest(dir: Vec3) -> Self {
+        let dir = dir.abs();
+        if dir.y >= dir.x && dir.y >= dir.z {
+            Self::Y
+        } else if dir.x >= dir.z {
+            Self::X
+        } else {
+            Self::Z
+        }
+    }
+}
+
+impl Facing {
+    /// The horizontal direction `dir` is closest to. North is towards -Z and east towards +X.
+    #[must_use]
+    pub fn nearest(dir: Vec3) -> Self {
+        if dir.x.abs() > dir.z.abs() {
+            if dir.x > 0.0 {
+                Self::East
+            } else {
+                Self::West
+            }
+        } else if dir.z > 0.0 {
+            Self::South
+        } else {
+            Self::North
+        }
+    }
+}
+
+/// Per-voxel block state, packed into a byte. How it is interpreted depends on the
+/// [`StateKind`] of the block.
+///
+/// | Bits | Meaning                               |
+/// |------|---------------------------------------|
+/// | 0-1  | Axis or facing                        |
+/// | 2    | Open                                  |
+/// | 3-5  | Growth stage                          |
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlockState(pub u8);
+
+impl BlockState {
+    pub const MAX_GROWTH: u8 = 7;
+
+    #[must_use]
+    pub const fn with_axis(self, axis: Axis) -> Self {
+        Self((self.0 & !0b11) | axis as u8)
+    }
+
+    #[must_use]
+    pub const fn axis(self) -> Axis {
+        match self.0 & 0b11 {
+            1 => Axis::X,
+            2 => Axis::Z,
+            _ => Axis::Y,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_facing(self, facing: Facing) -> Self {
+        Self((self.0 & !0b11) | facing as u8)
+    }
+
+    #[must_use]
+    pub const fn facing(self) -> Facing {
+        match self.0 & 0b11 {
+            0 => Facing::North,
+            1 => Facing::East,
+            2 => Facing::South,
+            _ => Facing::West,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_open(self, open: bool) -> Self {
+        Self((self.0 & !0b100) | (open as u8) << 2)
+    }
+
+    #[must_use]
+    pub const fn open(self) -> bool {
+        self.0 & 0b100 != 0
+    }
+
+    #[must_use]
+    pub const fn with_growth(self, growth: u8) -> Self {
+        Self((self.0 & !0b11_1000) | ((growth & 0b111) << 3))
+    }
+
+    #[must_use]
+    pub const fn growth(self) -> u8 {
+        (self.0 >> 3) & 0b111
+    }
+
+    /// State of a block of the given kind placed against a face with the given `normal` by a
+    /// player looking towards `look`. It lies along the axis of the face, and faces the way
+    /// the player looks, so that ladders hang on the wall they are placed against.
+    #[must_use]
+    pub fn placed(kind: StateKind, normal: IVec3, look: Vec3) -> Self {
+        match kind {
+            StateKind::Axis => Self::default().with_axis(Axis::nearest(normal.as_vec3())),
+            StateKind::Facing | StateKind::Door => {
+                Self::default().with_facing(Facing::nearest(look))
+            }
+            StateKind::None | StateKind::Crop => Self::default(),
+        }
+    }
+
+    /// Transform applied to the unit cube model of a block centered on the origin.
+    #[must_use]
+    pub fn model_transform(self, kind: StateKind) -> Affine3A {
+        let facing = |facing: Facing| {
+            Quat::from_rotation_y(-(facing as u8 as f32) * std::f32::consts::FRAC_PI_2)
+        };
+        match kind {
+            StateKind::None => Affine3A::IDENTITY,
+            StateKind::Axis => Affine3A::from_quat(match self.axis() {
+                Axis::Y => Quat::IDENTITY,
+                Axis::X => Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+                Axis::Z => Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+            }),
+            StateKind::Facing => Affine3A::from_quat(facing(self.facing())),
+            // Open doors swing a quarter turn on their hinge.
+            StateKind::Door => Affine3A::from_quat(if self.open() {
+                facing(self.facing()) * Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)
+            } else {
+                facing(self.facing())
+            }),
+            // Crops grow upwards from the bottom of their voxel.
+            StateKind::Crop => {
+                let height = f32::from(self.growth() + 1) / f32::from(Self::MAX_GROWTH + 1);
+                Affine3A::from_scale_rotation_translation(
+                    Vec3::new(1.0, height, 1.0),
+                    Quat::IDENTITY,
+                    Vec3::new(0.0, (height - 1.0) / 2.0, 0.0),
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, VecDeque},
+};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use super::{
+    block,
+    tick::BlockTicks,
+    world::{VoxelID, VoxelWorld},
+};
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// Sent when blocks around `center` lost their support, and again when they cave in.
+#[derive(Clone, Copy, Debug)]
+pub enum CaveInEvent {
+    /// The blocks will cave in after [`StructuralSupport::warning_ticks`], unless they are
+    /// supported again by then.
+    Warning {
+        center: IVec3,
+        blocks: usize,
+    },
+    Collapse {
+        center: IVec3,
+        blocks: usize,
+    },
+}
+
+/// Optional structural support simulation, disabled by default. Blocks with a
+/// [`block::BlockDef::support`] carry it up the columns they stand in, losing one for every
+/// block it reaches sideways or downwards. Whatever a change leaves without support caves
+/// in, falling as physics bodies; blocks that were already hanging before it stay.
+///
+/// Everything is evaluated in a deterministic order from the state of the world, so the
+/// outcome of a given world and sequence of changes is always the same.
+#[derive(Resource)]
+pub struct StructuralSupport {
+    pub enabled: bool,
+    /// How far from a change, in blocks, support is evaluated. Blocks on the edge of that
+    /// area are assumed to be held by the rest of the world.
+    pub radius: i32,
+    /// Ticks between the warning and the cave-in.
+    pub warning_ticks: u64,
+    /// Most blocks caving in on a single tick. The rest follow on the next ticks, bottom
+    /// to top.
+    pub max_collapse: usize,
+    /// Areas waiting to cave in, by due tick and then in the order they were scheduled.
+    pending: BTreeMap<(u64, u64), (IVec3, Vec<IVec3>)>,
+    scheduled: u64,
+    /// Blocks of the pending areas.
+    doomed: HashSet<IVec3>,
+}
+
+impl Default for StructuralSupport {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 8,
+            warning_ticks: 40,
+            max_collapse: 256,
+            pending: BTreeMap::new(),
+            scheduled: 0,
+            doomed: HashSet::default(),
+        }
+    }
+}
+
+impl StructuralSupport {
+    /// Checks the surroundings of the blocks removed during the last tick, and caves in the
+    /// areas whose warning ran out. Returns the events to send.
+    pub fn step(
+        &mut self,
+        world: &mut VoxelWorld,
+        ticks: &mut BlockTicks,
+        removed: &[(IVec3, VoxelID)],
+    ) -> Vec<CaveInEvent> {
+        let mut events = Vec::new();
+        if !self.enabled {
+            return events;
+        }
+
+        // Only blocks replaced by something weaker can take support away. A position
+        // replaced several times keeps the voxel it held at the start of the tick.
+        let removed = removed.iter().rev().copied().collect::<HashMap<_, _>>();
+        let removed = removed
+            .into_iter()
+            .filter_map(|(pos, voxel)| Some((pos, block::def(voxel).support?)))
+            .filter(|&(pos, support)| supports(world, pos) < Some(support))
+            .collect::<HashMap<_, _>>();
+        if !removed.is_empty() {
+            // Evaluate every change of the tick at once, and compare with the support the
+            // area had before them.
+            let mut sources = removed.keys().copied().collect::<Vec<_>>();
+            sources.sort_by_key(|pos| (pos.y, pos.x, pos.z));
+            let before = unsupported(world, &sources, self.radius, &removed)
+                .into_iter()
+                .collect::<HashSet<_>>();
+            let blocks = unsupported(world, &sources, self.radius, &HashMap::default())
+                .into_iter()
+                .filter(|pos| !before.contains(pos) && !self.doomed.contains(pos))
+                .collect::<Vec<_>>();
+            if !blocks.is_empty() {
+                let center = center(&blocks);
+                events.push(CaveInEvent::Warning {
+                    center,
+                    blocks: blocks.len(),
+                });
+                self.schedule(ticks.tick() + self.warning_ticks, center, blocks);
+            }
+        }
+
+        while let Some(entry) = self.pending.first_entry() {
+            if entry.key().0 > ticks.tick() {
+                break;
+            }
+            let (center, blocks) = entry.remove();
+            for pos in &blocks {
+                self.doomed.remove(pos);
+            }
+
+            // Some of the blocks may have been propped up or removed since the warning.
+            let hanging = unsupported(world, &blocks, self.radius, &HashMap::default())
+                .into_iter()
+                .collect::<HashSet<_>>();
+            let mut blocks = blocks
+                .into_iter()
+                .filter(|pos| hanging.contains(pos))
+                .collect::<Vec<_>>();
+            if blocks.is_empty() {
+                continue;
+            }
+            let rest = blocks.split_off(blocks.len().min(self.max_collapse));
+            if !rest.is_empty() {
+                self.schedule(ticks.tick() + 1, center, rest);
+            }
+            events.push(CaveInEvent::Collapse {
+                center,
+                blocks: blocks.len(),
+            });
+            for pos in blocks {
+                if let Some(voxel) = world.get(pos) {
+                    let state = world.state(pos);
+                    world.set(pos, None);
+                    ticks.fall(pos, voxel, state);
+                }
+            }
+        }
+        events
+    }
+
+    fn schedule(&mut self, due: u64, center: IVec3, blocks: Vec<IVec3>) {
+        self.doomed.extend(blocks.iter().copied());
+        self.pending.insert((due, self.scheduled), (center, blocks));
+        self.scheduled += 1;
+    }
+}
+
+/// Middle of the box around `blocks`.
+fn center(blocks: &[IVec3]) -> IVec3 {
+    let (min, max) = bounds(blocks);
+    (min + max) / 2
+}
+
+fn bounds(blocks: &[IVec3]) -> (IVec3, IVec3) {
+    blocks.iter().fold(
+        (IVec3::splat(i32::MAX), IVec3::splat(i32::MIN)),
+        |(min, max), &pos| (min.min(pos), max.max(pos)),
+    )
+}
+
+/// Support of the voxel at `pos`, if it takes part in the simulation.
+fn supports(world: &VoxelWorld, pos: IVec3) -> Option<u8> {
+    world.get(pos).and_then(|voxel| block::def(voxel).support)
+}
+
+/// Finds the blocks connected to `sources` and their neighbours that are not supported,
+/// looking at most `radius` blocks away from them. `restored` overrides the support of
+/// some positions, to evaluate the world as it was before they changed. The blocks are
+/// sorted bottom to top.
+#[must_use]
+pub fn unsupported(
+    world: &VoxelWorld,
+    sources: &[IVec3],
+    radius: i32,
+    restored: &HashMap<IVec3, u8>,
+) -> Vec<IVec3> {
+    // Gather the blocks connected to the sources, and those holding them up: blocks on the
+    // ground, next to unloaded chunks, or on the edge of the area.
+    let (min, max) = bounds(sources);
+    let mut region = HashMap::default();
+    let mut anchors = Vec::new();
+    let mut queue = sources
+        .iter()
+        .flat_map(|&pos| std::iter::once(pos).chain(NEIGHBOURS.iter().map(move |&dir| pos + dir)))
+        .collect::<VecDeque<_>>();
+    while let Some(next) = queue.pop_front() {
+        if region.contains_key(&next) {
+            continue;
+        }
+        let Some(support) = restored
+            .get(&next)
+            .copied()
+            .or_else(|| supports(world, next))
+        else {
+            continue;
+        };
+        region.insert(next, support);
+        let anchored = (min - next).max(next - max).max_element() >= radius
+            || next.y == 0
+            || NEIGHBOURS.iter().any(|&dir| !world.is_loaded(next + dir));
+        if anchored {
+            anchors.push(next);
+        } else {
+            queue.extend(NEIGHBOURS.iter().map(|&dir| next + dir));
+        }
+    }
+
+    // Spread support from the anchors, strongest first. It carries straight up a column, but
+    // every step sideways or downwards costs one.
+    let key = |pos: IVec3| Reverse((pos.y, pos.x, pos.z));
+    let mut levels = HashMap::<IVec3, u8>::default();
+    let mut heap = BinaryHeap::new();
+    for anchor in anchors {
+        let support = region[&anchor];
+        if support > 0 {
+            levels.insert(anchor, support);
+            heap.push((support, key(anchor)));
+        }
+    }
+    while let Some((level, Reverse((y, x, z)))) = heap.pop() {
+        let current = IVec3::new(x, y, z);
+        if levels.get(&current) != Some(&level) {
+            continue;
+        }
+        for dir in NEIGHBOURS {
+            let next = current + dir;
+            let Some(&support) = region.get(&next) else {
+                continue;
+            };
+            let spread = if dir == IVec3::Y {
+                level.min(support)
+            } else {
+                (level - 1).min(support)
+            };
+            if spread > levels.get(&next).copied().unwrap_or_default() {
+                levels.insert(next, spread);
+                heap.push((spread, key(next)));
+            }
+        }
+    }
+
+    let mut blocks = region
+        .into_keys()
+        .filter(|pos| !levels.contains_key(pos))
+        .collect::<Vec<_>>();
+    blocks.sort_by_key(|pos| (pos.y, pos.x, pos.z));
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::terrain::world::{Chunk, CHUNK_WIDTH};
+
+    /// A world of a single chunk with a stone floor at the bottom, and a pillar of stone
+    /// rising from it at each of `pillars` to hold a beam running east from the first one.
+    fn beam(pillars: &[IVec3], length: i32) -> VoxelWorld {
+        let mut world = VoxelWorld::default();
+        let mut chunk = Chunk::new();
+        for (x, z) in (0..CHUNK_WIDTH).flat_map(|x| (0..CHUNK_WIDTH).map(move |z| (x, z))) {
+            chunk.data[x][0][z] = Some(block::STONE);
+        }
+        world.chunks.insert(IVec2::ZERO, chunk);
+        for &top in pillars {
+            for y in 1..=top.y {
+                world.set(IVec3::new(top.x, y, top.z), Some(block::STONE));
+            }
+        }
+        for x in 1..=length {
+            world.set(pillars[0] + IVec3::X * x, Some(block::STONE));
+        }
+        world.take_updates();
+        world
+    }
+
+    fn enabled() -> StructuralSupport {
+        StructuralSupport {
+            enabled: true,
+            ..default()
+        }
+    }
+
+    fn run(
+        world: &mut VoxelWorld,
+        ticks: &mut BlockTicks,
+        support: &mut StructuralSupport,
+        count: u64,
+    ) -> Vec<CaveInEvent> {
+        (0..count)
+            .flat_map(|_| {
+                let updates = world.take_updates();
+                let removed = world.take_removed();
+                ticks.step(world, &updates);
+                support.step(world, ticks, &removed)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn support_runs_out_along_a_beam() {
+        let top = IVec3::new(4, 3, 8);
+        let world = beam(&[top], 4);
+
+        // Stone carries four blocks of support, so the fourth block of the beam is one too far.
+        let end = top + IVec3::X * 4;
+        assert_eq!(
+            unsupported(&world, &[end + IVec3::X], 8, &HashMap::default()),
+            vec![end]
+        );
+    }
+
+    #[test]
+    fn blocks_held_by_another_pillar_stay() {
+        let (first, second) = (IVec3::new(4, 3, 8), IVec3::new(7, 3, 8));
+        let mut world = beam(&[first, second], 3);
+        let mut ticks = BlockTicks::default();
+        let mut support = enabled();
+        let warning_ticks = support.warning_ticks;
+        world.set(first - IVec3::Y, None);
+        let events = run(&mut world, &mut ticks, &mut support, warning_ticks + 1);
+
+        assert!(events.is_empty());
+        assert_eq!(world.get(first), Some(block::STONE));
+        assert!(ticks.take_falling().is_empty());
+    }
+
+    #[test]
+    fn unsupported_blocks_cave_in_after_a_warning() {
+        let top = IVec3::new(4, 3, 8);
+        let mut world = beam(&[top], 3);
+        let mut ticks = BlockTicks::default();
+        let mut support = enabled();
+        let warning_ticks = support.warning_ticks;
+        world.set(top - IVec3::Y, None);
+
+        let events = run(&mut world, &mut ticks, &mut support, 1);
+        assert!(matches!(
+            events[..],
+            [CaveInEvent::Warning { center, blocks: 4 }] if center == top + IVec3::X
+        ));
+        assert_eq!(world.get(top), Some(block::STONE));
+
+        let events = run(&mut world, &mut ticks, &mut support, warning_ticks);
+        assert!(matches!(
+            events[..],
+            [CaveInEvent::Collapse { blocks: 4, .. }]
+        ));
+        let falling = ticks.take_falling();
+        assert_eq!(falling.len(), 4);
+        for (pos, voxel, _) in falling {
+            assert_eq!(voxel, block::STONE);
+            assert_eq!(world.get(pos), None);
+        }
+        // The stump of the pillar is still standing on the floor.
+        assert_eq!(world.get(top - IVec3::Y * 2), Some(block::STONE));
+    }
+
+    #[test]
+    fn blocks_propped_up_during_the_warning_stay() {
+        let top = IVec3::new(4, 3, 8);
+        let mut world = beam(&[top], 3);
+        let mut ticks = BlockTicks::default();
+        let mut support = enabled();
+        let warning_ticks = support.warning_ticks;
+        world.set(top - IVec3::Y, None);
+        run(&mut world, &mut ticks, &mut support, 1);
+
+        world.set(top + IVec3::new(3, -1, 0), Some(block::STONE));
+        world.set(top + IVec3::new(3, -2, 0), Some(block::STONE));
+        let events = run(&mut world, &mut ticks, &mut support, warning_ticks);
+
+        assert!(events.is_empty());
+        assert!(ticks.take_falling().is_empty());
+        assert_eq!(world.get(top), Some(block::STONE));
+    }
+
+    #[test]
+    fn blocks_left_hanging_before_the_change_stay() {
+        let top = IVec3::new(4, 3, 8);
+        let mut world = beam(&[top], 5);
+        let mut ticks = BlockTicks::default();
+        let mut support = enabled();
+        let warning_ticks = support.warning_ticks;
+        // The end of the beam is already past what stone carries, but it isn't this change
+        // that left it hanging.
+        world.set(top + IVec3::new(1, 1, 0), Some(block::STONE));
+        run(&mut world, &mut ticks, &mut support, 1);
+        world.set(top + IVec3::new(1, 1, 0), None);
+        let events = run(&mut world, &mut ticks, &mut support, warning_ticks + 1);
+
+        assert!(events.is_empty());
+        assert_eq!(world.get(top + IVec3::X * 5), Some(block::STONE));
+    }
+
+    #[test]
+    fn large_cave_ins_are_spread_over_several_ticks() {
+        let top = IVec3::new(4, 3, 8);
+        let mut world = beam(&[top], 3);
+        let mut ticks = BlockTicks::default();
+        let mut support = StructuralSupport {
+            max_collapse: 3,
+            ..enabled()
+        };
+        let warning_ticks = support.warning_ticks;
+        world.set(top - IVec3::Y, None);
+        run(&mut world, &mut ticks, &mut support, warning_ticks);
+
+        let events = run(&mut world, &mut ticks, &mut support, 1);
+        assert!(matches!(
+            events[..],
+            [CaveInEvent::Collapse { blocks: 3, .. }]
+        ));
+        assert_eq!(world.get(top + IVec3::X * 3), Some(block::STONE));
+
+        let events = run(&mut world, &mut ticks, &mut support, 1);
+        assert!(matches!(
+            events[..],
+            [CaveInEvent::Collapse { blocks: 1, .. }]
+        ));
+        assert_eq!(world.get(top + IVec3::X * 3), None);
+        assert_eq!(ticks.take_falling().len(), 4);
+    }
+}
@@ -0,0 +1,42 @@
+/// How a block picks the part of its texture drawn on each face.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureRule {
+    /// The whole texture, as is.
+    Fixed,
+    /// One of `variants` tiles laid out side by side, turned by a quarter turn or more if
+    /// `rotate` is set. Both are picked from the position of the block.
+    Random { rotate: bool, variants: u8 },
+    /// One of 16 tiles in a 4x4 grid, picked by which edges of the face continue into the
+    /// same block. Tile `i` is in column `i % 4` and row `i / 4`, where bits 0 to 3 of `i`
+    /// stand for the left, right, top and bottom edges.
+    Connected,
+}
+
+impl TextureRule {
+    /// Maps face UVs within 0..1 to the region of the texture picked for the face.
+    /// `hash` is the hash of the block position, and `connections` tells whether the face
+    /// continues past its left, right, top and bottom edges.
+    #[must_use]
+    pub fn apply(self, uvs: [[f32; 2]; 4], hash: u64, connections: [bool; 4]) -> [[f32; 2]; 4] {
+        match self {
+            Self::Fixed => uvs,
+            Self::Random { rotate, variants } => {
+                let turns = if rotate { hash % 4 } else { 0 };
+                let variant = (hash >> 2) % u64::from(variants.max(1));
+                uvs.map(|uv| {
+                    let [u, v] = (0..turns).fold(uv, |[u, v], _| [v, 1.0 - u]);
+                    [(variant as f32 + u) / f32::from(variants.max(1)), v]
+                })
+            }
+            Self::Connected => {
+                let tile = connections
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &connected)| connected)
+                    .fold(0, |tile, (bit, _)| tile | 1 << bit);
+                let (column, row) = ((tile % 4) as f32, (tile / 4) as f32);
+                uvs.map(|[u, v]| [(column + u) / 4.0, (row + v) / 4.0])
+            }
+        }
+    }
+}
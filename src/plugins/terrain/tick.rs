@@ -0,0 +1,306 @@
+use std::collections::BTreeMap;
+
+use bevy::{prelude::*, utils::HashSet};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    block, fluid, gravity,
+    state::BlockState,
+    support::{CaveInEvent, StructuralSupport},
+    world::{position_hash, VoxelID, VoxelWorld, CHUNK_HEIGHT, CHUNK_WIDTH},
+};
+
+/// Height of the sections random ticks are spread over. Sections are cubes.
+const SECTION_HEIGHT: usize = CHUNK_WIDTH;
+/// Light level crops need to grow.
+const GROWTH_LIGHT: u8 = 9;
+
+/// Block updates running at [`super::TICK_RATE`], independently of the frame rate.
+///
+/// Random ticks pick a few voxels of every section of every loaded chunk each tick, for slow
+/// processes like crop growth. Compressed chunks are only decompressed if a picked voxel
+/// changes. The picks of a section are drawn from a generator seeded with the world's seed,
+/// the section's position and the tick, so they don't depend on the order chunks are visited
+/// in. Scheduled updates let a block ask to be updated again after a delay. They are
+/// processed in the order they were scheduled. Both keep block updates deterministic for a
+/// given world and sequence of changes.
+#[derive(Resource)]
+pub struct BlockTicks {
+    /// Voxels picked per section each tick.
+    pub random_ticks: usize,
+    /// Maximum number of scheduled updates run per tick. The rest carry over to the next tick.
+    pub budget: usize,
+    tick: u64,
+    next_id: u64,
+    queue: BTreeMap<(u64, u64), IVec3>,
+    scheduled: HashSet<IVec3>,
+    falling: Vec<(IVec3, VoxelID, BlockState)>,
+    seed: u64,
+}
+
+impl Default for BlockTicks {
+    fn default() -> Self {
+        Self {
+            random_ticks: 3,
+            budget: 4096,
+            tick: 0,
+            next_id: 0,
+            queue: BTreeMap::new(),
+            scheduled: HashSet::default(),
+            falling: Vec::new(),
+            seed: 0,
+        }
+    }
+}
+
+impl BlockTicks {
+    /// Updates the block at `pos` in `delay` ticks, unless an update is already pending there.
+    pub fn schedule(&mut self, pos: IVec3, delay: u64) {
+        if self.scheduled.insert(pos) {
+            self.queue.insert((self.tick + delay, self.next_id), pos);
+            self.next_id += 1;
+        }
+    }
+
+    /// Seeds the random ticks, with the seed of the world they run in.
+    pub const fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    #[must_use]
+    pub const fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Records a block taken out of the world at `pos` to fall as a physics body.
+    pub fn fall(&mut self, pos: IVec3, voxel: VoxelID, state: BlockState) {
+        self.falling.push((pos, voxel, state));
+    }
+
+    /// Returns the blocks that started falling since the last call.
+    pub fn take_falling(&mut self) -> Vec<(IVec3, VoxelID, BlockState)> {
+        std::mem::take(&mut self.falling)
+    }
+
+    /// Advances by one tick, given the voxels replaced since the last one.
+    pub fn step(&mut self, world: &mut VoxelWorld, updates: &[IVec3]) {
+        self.tick += 1;
+
+        // Wake up whatever may react to the replaced voxels.
+        for &pos in updates {
+            fluid::notify(world, self, pos);
+            gravity::notify(world, self, pos);
+        }
+
+        let chunks = world
+            .chunks
+            .keys()
+            .copied()
+            .chain(world.compressed_chunks().map(|(chunk_pos, _)| chunk_pos))
+            .collect::<Vec<_>>();
+        for chunk_pos in chunks {
+            for section in 0..CHUNK_HEIGHT / SECTION_HEIGHT {
+                let origin = IVec3::new(
+                    chunk_pos.x * CHUNK_WIDTH as i32,
+                    (section * SECTION_HEIGHT) as i32,
+                    chunk_pos.y * CHUNK_WIDTH as i32,
+                );
+                let mut rng = StdRng::seed_from_u64(
+                    self.seed
+                        ^ position_hash(origin)
+                        ^ self.tick.wrapping_mul(0x9E37_79B9_7F4A_7C15),
+                );
+                for _ in 0..self.random_ticks {
+                    let offset = IVec3::new(
+                        rng.gen_range(0..CHUNK_WIDTH as i32),
+                        rng.gen_range(0..SECTION_HEIGHT as i32),
+                        rng.gen_range(0..CHUNK_WIDTH as i32),
+                    );
+                    random_tick(world, origin + offset);
+                }
+            }
+        }
+
+        for _ in 0..self.budget {
+            let Some(entry) = self.queue.first_entry() else {
+                break;
+            };
+            if entry.key().0 > self.tick {
+                break;
+            }
+            let pos = entry.remove();
+            self.scheduled.remove(&pos);
+            self.update(world, pos);
+        }
+    }
+
+    /// Runs the scheduled update of the block at `pos`.
+    fn update(&mut self, world: &mut VoxelWorld, pos: IVec3) {
+        let Some(voxel) = world.get(pos) else {
+            return;
+        };
+        if fluid::Fluid::from_voxel(voxel).is_some() {
+            fluid::update(world, self, pos);
+        } else if block::def(voxel).gravity {
+            gravity::update(world, self, pos);
+        }
+    }
+}
+
+fn random_tick(world: &mut VoxelWorld, pos: IVec3) {
+    if world.get(pos) == Some(block::WHEAT) {
+        let state = world.state(pos);
+        let light = world.light(pos);
+        if state.growth() < BlockState::MAX_GROWTH && (light >> 4).max(light & 0xF) >= GROWTH_LIGHT
+        {
+            world.set_state(pos, state.with_growth(state.growth() + 1));
+        }
+    }
+}
+
+pub fn tick_system(
+    mut world: ResMut<VoxelWorld>,
+    mut ticks: ResMut<BlockTicks>,
+    mut support: ResMut<StructuralSupport>,
+    mut cave_ins: EventWriter<CaveInEvent>,
+) {
+    let updates = world.take_updates();
+    let removed = world.take_removed();
+    ticks.step(&mut world, &updates);
+    cave_ins.send_batch(support.step(&mut world, &mut ticks, &removed));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::terrain::world::Chunk;
+
+    /// A world of a single empty chunk holding `voxel` at `blocks`.
+    fn single_chunk(voxel: VoxelID, blocks: &[IVec3]) -> VoxelWorld {
+        let mut chunk = Chunk::new();
+        for pos in blocks {
+            chunk.data[pos.x as usize][pos.y as usize][pos.z as usize] = Some(voxel);
+        }
+        let mut world = VoxelWorld::default();
+        world.chunks.insert(IVec2::ZERO, chunk);
+        world
+    }
+
+    /// A sunlit field of wheat filling the bottom section of the chunks at `chunks`.
+    fn field(chunks: &[IVec2]) -> VoxelWorld {
+        let mut world = VoxelWorld::default();
+        for &chunk_pos in chunks {
+            let mut chunk = Chunk::new();
+            for (x, y) in (0..CHUNK_WIDTH).flat_map(|x| (0..SECTION_HEIGHT).map(move |y| (x, y))) {
+                for z in 0..CHUNK_WIDTH {
+                    chunk.data[x][y][z] = Some(block::WHEAT);
+                    chunk.light[x][y][z] = 0xF0;
+                }
+            }
+            world.chunks.insert(chunk_pos, chunk);
+        }
+        world
+    }
+
+    fn growth(world: &VoxelWorld, chunks: &[IVec2]) -> Vec<u8> {
+        chunks
+            .iter()
+            .flat_map(|chunk_pos| {
+                let origin = IVec3::new(chunk_pos.x, 0, chunk_pos.y) * CHUNK_WIDTH as i32;
+                (0..CHUNK_WIDTH as i32)
+                    .flat_map(|x| (0..SECTION_HEIGHT as i32).map(move |y| (x, y)))
+                    .flat_map(move |(x, y)| {
+                        (0..CHUNK_WIDTH as i32).map(move |z| origin + IVec3::new(x, y, z))
+                    })
+            })
+            .map(|pos| world.state(pos).growth())
+            .collect()
+    }
+
+    fn grow(world: &mut VoxelWorld, seed: u64, count: usize) {
+        let mut ticks = BlockTicks {
+            random_ticks: 64,
+            ..default()
+        };
+        ticks.reseed(seed);
+        for _ in 0..count {
+            ticks.step(world, &[]);
+        }
+    }
+
+    #[test]
+    fn updates_due_together_run_in_scheduling_order() {
+        let sand = [
+            IVec3::new(3, 10, 3),
+            IVec3::new(1, 10, 1),
+            IVec3::new(2, 10, 2),
+        ];
+        let mut world = single_chunk(block::SAND, &sand);
+        let mut ticks = BlockTicks::default();
+        for pos in sand {
+            ticks.schedule(pos, 1);
+        }
+        ticks.step(&mut world, &[]);
+
+        let fallen = ticks
+            .take_falling()
+            .into_iter()
+            .map(|(pos, _, _)| pos)
+            .collect::<Vec<_>>();
+        assert_eq!(fallen, sand);
+    }
+
+    #[test]
+    fn updates_wait_for_their_delay() {
+        let sand = IVec3::new(3, 10, 3);
+        let mut world = single_chunk(block::SAND, &[sand]);
+        let mut ticks = BlockTicks::default();
+        ticks.schedule(sand, 3);
+        // Already pending, so the earlier update isn't added.
+        ticks.schedule(sand, 1);
+
+        for _ in 0..2 {
+            ticks.step(&mut world, &[]);
+            assert!(ticks.take_falling().is_empty());
+            assert_eq!(world.get(sand), Some(block::SAND));
+        }
+        ticks.step(&mut world, &[]);
+        assert_eq!(ticks.take_falling().len(), 1);
+        assert_eq!(world.get(sand), None);
+    }
+
+    #[test]
+    fn random_ticks_only_depend_on_the_seed() {
+        let chunks = [IVec2::ZERO, IVec2::X, IVec2::NEG_Y];
+        let mut reversed = chunks;
+        reversed.reverse();
+        let run = |seed: u64, chunks: &[IVec2]| {
+            let mut world = field(chunks);
+            grow(&mut world, seed, 20);
+            growth(&world, &[IVec2::ZERO, IVec2::X, IVec2::NEG_Y])
+        };
+
+        let grown = run(7, &chunks);
+        assert!(grown.iter().any(|&growth| growth > 0));
+        // Loading the chunks in another order changes nothing.
+        assert_eq!(run(7, &reversed), grown);
+        assert_ne!(run(8, &chunks), grown);
+    }
+
+    #[test]
+    fn compressed_chunks_are_ticked() {
+        let mut world = field(&[IVec2::ZERO]);
+        world.compress(IVec2::ZERO, Vec::new());
+        grow(&mut world, 7, 20);
+        assert!(growth(&world, &[IVec2::ZERO])
+            .iter()
+            .any(|&growth| growth > 0));
+
+        let mut uncompressed = field(&[IVec2::ZERO]);
+        grow(&mut uncompressed, 7, 20);
+        assert_eq!(
+            growth(&world, &[IVec2::ZERO]),
+            growth(&uncompressed, &[IVec2::ZERO])
+        );
+    }
+}
@@ -1,208 +1,1224 @@
+use std::borrow::Cow;
+
 use bevy::{
+    math::Affine3A,
     prelude::*,
     render::{mesh::Indices, render_resource::PrimitiveTopology},
+    utils::{HashMap, HashSet},
 };
+use bevy_rapier3d::prelude::Collider;
 use noise::{NoiseFn, OpenSimplex};
 
+use super::{
+    block, block_entity::BlockEntityData, cache::CompressedChunk, fluid, light, shape::Shape,
+    state::BlockState, texture::TextureRule,
+};
+
 pub const CHUNK_WIDTH: usize = 16;
 pub const CHUNK_HEIGHT: usize = 256;
+/// Air pockets below this height are flooded with lava during generation.
+pub const LAVA_LEVEL: usize = 10;
+
+pub type VoxelID = u64;
 
-type VoxelID = u64;
+/// Everything stored for a single voxel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub voxel: Option<VoxelID>,
+    pub fluid_level: u8,
+    pub light: u8,
+    pub state: BlockState,
+}
 
+#[derive(Clone)]
 pub struct Chunk {
     pub data: Vec<Vec<Vec<Option<VoxelID>>>>,
+    pub fluid_levels: Vec<Vec<Vec<u8>>>,
+    /// Sky light in the high nibble, block light in the low nibble.
+    pub light: Vec<Vec<Vec<u8>>>,
+    pub states: Vec<Vec<Vec<BlockState>>>,
 }
 
 impl Chunk {
+    /// Approximate number of bytes held by the data of a chunk.
+    pub const MEMORY_SIZE: usize =
+        CHUNK_WIDTH * CHUNK_HEIGHT * CHUNK_WIDTH * (std::mem::size_of::<Option<VoxelID>>() + 3);
+
     #[must_use]
     pub fn new() -> Self {
         Self {
             data: vec![vec![vec![None; CHUNK_WIDTH]; CHUNK_HEIGHT]; CHUNK_WIDTH],
+            fluid_levels: vec![vec![vec![0; CHUNK_WIDTH]; CHUNK_HEIGHT]; CHUNK_WIDTH],
+            light: vec![vec![vec![0; CHUNK_WIDTH]; CHUNK_HEIGHT]; CHUNK_WIDTH],
+            states: vec![vec![vec![BlockState::default(); CHUNK_WIDTH]; CHUNK_HEIGHT]; CHUNK_WIDTH],
         }
     }
 
-    pub fn generate(&mut self, noise: &OpenSimplex, off_pos: (usize, usize)) {
+    pub fn generate(&mut self, noise: &OpenSimplex, off_pos: IVec2) {
         for (x, y, z) in (0..CHUNK_WIDTH)
             .flat_map(|x| (0..CHUNK_HEIGHT / 2).map(move |y| (x, y)))
             .flat_map(|(x, y)| (0..CHUNK_WIDTH).map(move |z| (x as f32, y as f32, z as f32)))
-            .filter(|&(x, y, z)| {
-                noise.get([
-                    (off_pos.0 as f64 + f64::from(x)) / 16.0,
-                    f64::from(y) / 16.0,
-                    (off_pos.1 as f64 + f64::from(z)) / 16.0,
-                ]) >= 0.0
-            })
         {
-            self.data[x as usize][y as usize][z as usize] = Some(1);
+            let solid = noise.get([
+                (f64::from(off_pos.x) + f64::from(x)) / 16.0,
+                f64::from(y) / 16.0,
+                (f64::from(off_pos.y) + f64::from(z)) / 16.0,
+            ]) >= 0.0;
+            let (x, y, z) = (x as usize, y as usize, z as usize);
+            if solid {
+                self.data[x][y][z] = Some(block::STONE);
+            } else if y < LAVA_LEVEL {
+                self.data[x][y][z] = Some(block::LAVA);
+                self.fluid_levels[x][y][z] = fluid::SOURCE_LEVEL;
+            }
         }
     }
 
-    pub fn create_mesh(&self) -> Mesh {
-        let mut positions = Vec::new();
-        let mut normals = Vec::new();
-        let mut uvs = Vec::new();
-        let mut indices = Vec::new();
-        let mut last_index = 0;
+    #[must_use]
+    pub fn cell(&self, x: usize, y: usize, z: usize) -> Cell {
+        Cell {
+            voxel: self.data[x][y][z],
+            fluid_level: self.fluid_levels[x][y][z],
+            light: self.light[x][y][z],
+            state: self.states[x][y][z],
+        }
+    }
 
-        for pos in (0..CHUNK_WIDTH)
-            .flat_map(|x| (0..CHUNK_HEIGHT).map(move |y| (x, y)))
-            .flat_map(|(x, y)| (0..CHUNK_WIDTH).map(move |z| (x, y, z)))
-        {
-            if self.data[pos.0][pos.1][pos.2].is_some() {
-                for (_, p, n, u) in vec![
-                    // Front
-                    (
-                        (0i32, 0i32, 1i32),
-                        [
-                            [pos.0 as f32 - 0.5, pos.1 as f32 - 0.5, pos.2 as f32 + 0.5],
-                            [pos.0 as f32 + 0.5, pos.1 as f32 - 0.5, pos.2 as f32 + 0.5],
-                            [pos.0 as f32 + 0.5, pos.1 as f32 + 0.5, pos.2 as f32 + 0.5],
-                            [pos.0 as f32 - 0.5, pos.1 as f32 + 0.5, pos.2 as f32 + 0.5],
-                        ],
-                        [
-                            [0.0, 0.0, 1.0],
-                            [0.0, 0.0, 1.0],
-                            [0.0, 0.0, 1.0],
-                            [0.0, 0.0, 1.0],
-                        ],
-                        [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
-                    ),
-                    // Back
-                    (
-                        (0i32, 0i32, -1i32),
-                        [
-                            [pos.0 as f32 - 0.5, pos.1 as f32 + 0.5, pos.2 as f32 - 0.5],
-                            [pos.0 as f32 + 0.5, pos.1 as f32 + 0.5, pos.2 as f32 - 0.5],
-                            [pos.0 as f32 + 0.5, pos.1 as f32 - 0.5, pos.2 as f32 - 0.5],
-                            [pos.0 as f32 - 0.5, pos.1 as f32 - 0.5, pos.2 as f32 - 0.5],
-                        ],
-                        [
-                            [0.0, 0.0, -1.0],
-                            [0.0, 0.0, -1.0],
-                            [0.0, 0.0, -1.0],
-                            [0.0, 0.0, -1.0],
-                        ],
-                        [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
-                    ),
-                    // Right
-                    (
-                        (1i32, 0i32, 0i32),
-                        [
-                            [pos.0 as f32 + 0.5, pos.1 as f32 - 0.5, pos.2 as f32 - 0.5],
-                            [pos.0 as f32 + 0.5, pos.1 as f32 + 0.5, pos.2 as f32 - 0.5],
-                            [pos.0 as f32 + 0.5, pos.1 as f32 + 0.5, pos.2 as f32 + 0.5],
-                            [pos.0 as f32 + 0.5, pos.1 as f32 - 0.5, pos.2 as f32 + 0.5],
-                        ],
-                        [
-                            [1.0, 0.0, 0.0],
-                            [1.0, 0.0, 0.0],
-                            [1.0, 0.0, 0.0],
-                            [1.0, 0.0, 0.0],
-                        ],
-                        [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
-                    ),
-                    // Left
-                    (
-                        (-1i32, 0i32, 0i32),
-                        [
-                            [pos.0 as f32 - 0.5, pos.1 as f32 - 0.5, pos.2 as f32 + 0.5],
-                            [pos.0 as f32 - 0.5, pos.1 as f32 + 0.5, pos.2 as f32 + 0.5],
-                            [pos.0 as f32 - 0.5, pos.1 as f32 + 0.5, pos.2 as f32 - 0.5],
-                            [pos.0 as f32 - 0.5, pos.1 as f32 - 0.5, pos.2 as f32 - 0.5],
-                        ],
-                        [
-                            [-1.0, 0.0, 0.0],
-                            [-1.0, 0.0, 0.0],
-                            [-1.0, 0.0, 0.0],
-                            [-1.0, 0.0, 0.0],
-                        ],
-                        [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
-                    ),
-                    // Top
-                    (
-                        (0i32, 1i32, 0i32),
-                        [
-                            [pos.0 as f32 + 0.5, pos.1 as f32 + 0.5, pos.2 as f32 - 0.5],
-                            [pos.0 as f32 - 0.5, pos.1 as f32 + 0.5, pos.2 as f32 - 0.5],
-                            [pos.0 as f32 - 0.5, pos.1 as f32 + 0.5, pos.2 as f32 + 0.5],
-                            [pos.0 as f32 + 0.5, pos.1 as f32 + 0.5, pos.2 as f32 + 0.5],
-                        ],
-                        [
-                            [0.0, 1.0, 0.0],
-                            [0.0, 1.0, 0.0],
-                            [0.0, 1.0, 0.0],
-                            [0.0, 1.0, 0.0],
-                        ],
-                        [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
-                    ),
-                    // Bottom
-                    (
-                        (0i32, -1i32, 0i32),
-                        [
-                            [pos.0 as f32 + 0.5, pos.1 as f32 - 0.5, pos.2 as f32 + 0.5],
-                            [pos.0 as f32 - 0.5, pos.1 as f32 - 0.5, pos.2 as f32 + 0.5],
-                            [pos.0 as f32 - 0.5, pos.1 as f32 - 0.5, pos.2 as f32 - 0.5],
-                            [pos.0 as f32 + 0.5, pos.1 as f32 - 0.5, pos.2 as f32 - 0.5],
-                        ],
-                        [
-                            [0.0, -1.0, 0.0],
-                            [0.0, -1.0, 0.0],
-                            [0.0, -1.0, 0.0],
-                            [0.0, -1.0, 0.0],
-                        ],
-                        [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.032]],
-                    ),
-                ]
-                .iter()
-                .filter(|(face, _, _, _)| {
-                    if face.0.wrapping_abs() as u32 as usize > pos.0
-                        || face.1.wrapping_abs() as u32 as usize > pos.1
-                        || face.2.wrapping_abs() as u32 as usize > pos.2
-                    {
-                        true
-                    } else {
-                        fn add(u: usize, i: i32) -> usize {
-                            if i.is_negative() {
-                                u - i.wrapping_abs() as u32 as usize
-                            } else {
-                                u + i as usize
-                            }
-                        }
-
-                        let x: usize = add(pos.0, face.0);
-                        let y: usize = add(pos.1, face.1);
-                        let z: usize = add(pos.2, face.2);
-
-                        matches!(
-                            self.data
-                                .get(x)
-                                .and_then(|v| v.get(y))
-                                .and_then(|v| v.get(z)),
-                            None | Some(None)
-                        )
+    pub fn set_cell(&mut self, x: usize, y: usize, z: usize, cell: Cell) {
+        self.data[x][y][z] = cell.voxel;
+        self.fluid_levels[x][y][z] = cell.fluid_level;
+        self.light[x][y][z] = cell.light;
+        self.states[x][y][z] = cell.state;
+    }
+
+    /// Builds the meshes of the chunk at `chunk_pos` in `world`, indexed by
+    /// [`block::mesh_slot`]. `world` is used to look up the light in front of faces on the
+    /// chunk's edges.
+    pub fn create_mesh(&self, world: &VoxelWorld, chunk_pos: IVec2) -> Vec<Mesh> {
+        let origin = IVec3::new(
+            chunk_pos.x * CHUNK_WIDTH as i32,
+            0,
+            chunk_pos.y * CHUNK_WIDTH as i32,
+        );
+        let mut slots = (0..block::mesh_slots())
+            .map(|_| MeshBuilder::default())
+            .collect::<Vec<_>>();
+        let mut quads = Vec::new();
+
+        for center in Self::cells() {
+            let Some(voxel) = self.voxel(center) else {
+                continue;
+            };
+            let def = block::def(voxel);
+            quads.clear();
+            self.quads(
+                center,
+                |neighbour| block::hides(voxel, neighbour),
+                &mut quads,
+            );
+
+            for quad in &quads {
+                // Faces inside the voxel are lit by the voxel itself.
+                let front = origin + center + quad.boundary.unwrap_or(IVec3::ZERO);
+                let brightness = light::brightness(world.light(front));
+                // Only full blocks are occluded, partial shapes are evenly lit.
+                let ao = match quad.boundary {
+                    Some(normal) if def.shape.is_full() => quad
+                        .corners
+                        .map(|corner| vertex_ao(world, front, normal, corner)),
+                    _ => [3; 4],
+                };
+
+                let connections = match quad.boundary {
+                    Some(normal) if def.texture == TextureRule::Connected => {
+                        face_connections(world, origin + center, voxel, normal, quad)
                     }
-                }) {
-                    positions.extend_from_slice(p);
-                    normals.extend_from_slice(n);
-                    uvs.extend_from_slice(u);
-                    indices.extend_from_slice(&[
-                        last_index,
-                        last_index + 1,
-                        last_index + 2,
-                        last_index + 2,
-                        last_index + 3,
-                        last_index,
-                    ]);
-                    last_index += 4;
+                    _ => [false; 4],
+                };
+                let uvs = def
+                    .texture
+                    .apply(quad.uvs, position_hash(origin + center), connections);
+
+                // Split the quad along its darker diagonal, so that the same occlusion
+                // looks the same whichever way the face is rotated.
+                slots[block::mesh_slot(voxel)].push(
+                    quad.corners.map(|corner| center.as_vec3() + corner),
+                    quad.normal,
+                    uvs,
+                    ao.map(|ao| brightness * AO_CURVE[usize::from(ao)]),
+                    ao[0] + ao[2] > ao[1] + ao[3],
+                );
+            }
+        }
+
+        slots.into_iter().map(MeshBuilder::build).collect()
+    }
+
+    /// Builds a collider matching the shapes of the solid blocks of the chunk, if it has any.
+    #[must_use]
+    pub fn create_collider(&self) -> Option<Collider> {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut quads = Vec::new();
+
+        for center in Self::cells() {
+            if !self
+                .voxel(center)
+                .is_some_and(|voxel| block::def(voxel).solid)
+            {
+                continue;
+            }
+            quads.clear();
+            self.quads(
+                center,
+                |neighbour| {
+                    let def = block::def(neighbour);
+                    def.shape.is_full() && def.solid
+                },
+                &mut quads,
+            );
+            for quad in &quads {
+                let base = vertices.len() as u32;
+                vertices.extend(quad.corners.iter().map(|&corner| center.as_vec3() + corner));
+                indices.extend([[base, base + 1, base + 2], [base + 2, base + 3, base]]);
+            }
+        }
+
+        (!indices.is_empty()).then(|| Collider::trimesh(vertices, indices))
+    }
+
+    /// Appends the quads of the voxel at `pos`, relative to the center of the voxel.
+    /// Faces on the boundary of the voxel are skipped when the neighbour they are pressed
+    /// against is inside the chunk and `hidden_by` it.
+    fn quads(&self, pos: IVec3, hidden_by: impl Fn(VoxelID) -> bool, quads: &mut Vec<Quad>) {
+        let Some(voxel) = self.voxel(pos) else {
+            return;
+        };
+        let def = block::def(voxel);
+        let transform = self.state(pos).model_transform(def.state);
+
+        if def.shape == Shape::Cross {
+            quads.extend(CROSS.iter().map(|(corners, uvs)| Quad {
+                corners: corners.map(|corner| transform.transform_point3(Vec3::from(corner))),
+                uvs: *uvs,
+                normal: Vec3::Y,
+                boundary: None,
+            }));
+            return;
+        }
+
+        let neighbour = |dir: IVec3| {
+            Some(pos + dir)
+                .filter(|&neighbour| Self::contains(neighbour))
+                .and_then(|neighbour| self.voxel(neighbour))
+        };
+        let connections = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z].map(|dir| {
+            neighbour(dir)
+                .map(block::def)
+                .is_some_and(|def| matches!(def.shape, Shape::Cube | Shape::Fence) && def.solid)
+        });
+        for (min, max) in def.shape.boxes(connections) {
+            for face in &FACES {
+                let model = face
+                    .corners
+                    .map(|corner| min + (Vec3::from(corner) + 0.5) * (max - min));
+                let normal = rotate_normal(transform, face.normal);
+                let boundary = (model[0].dot(face.normal.as_vec3()) - 0.5).abs() < 1E-4;
+                if boundary && neighbour(normal).is_some_and(&hidden_by) {
+                    continue;
                 }
+                quads.push(Quad {
+                    corners: model.map(|corner| transform.transform_point3(corner)),
+                    uvs: model.map(|corner| face.uv(corner)),
+                    normal: normal.as_vec3(),
+                    boundary: boundary.then_some(normal),
+                });
             }
         }
+    }
+
+    /// Builds a reduced mesh of the chunk at `chunk_pos`, where every cube of `2^lod` voxels
+    /// on each side is merged into a single cell. A cell is solid if at least half of its
+    /// voxels are.
+    ///
+    /// Faces on the edges of the chunk are always emitted. This closes the mesh, so it acts
+    /// as a skirt hiding the cracks between neighbouring chunks of different detail.
+    ///
+    /// Everything is drawn in the opaque slot, the others are left empty.
+    pub fn create_lod_mesh(&self, world: &VoxelWorld, chunk_pos: IVec2, lod: u32) -> Vec<Mesh> {
+        if lod == 0 {
+            return self.create_mesh(world, chunk_pos);
+        }
+        let scale = 1 << lod;
+        let origin = IVec3::new(
+            chunk_pos.x * CHUNK_WIDTH as i32,
+            0,
+            chunk_pos.y * CHUNK_WIDTH as i32,
+        );
+        let (width, height) = (CHUNK_WIDTH / scale, CHUNK_HEIGHT / scale);
+        let cells = (0..width)
+            .map(|x| {
+                (0..height)
+                    .map(|y| {
+                        (0..width)
+                            .map(|z| {
+                                let solid = (0..scale)
+                                    .flat_map(|dx| (0..scale).map(move |dy| (dx, dy)))
+                                    .flat_map(|(dx, dy)| (0..scale).map(move |dz| (dx, dy, dz)))
+                                    .filter(|&(dx, dy, dz)| {
+                                        self.data[x * scale + dx][y * scale + dy][z * scale + dz]
+                                            .is_some()
+                                    })
+                                    .count();
+                                solid * 2 >= scale * scale * scale
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut mesh = MeshBuilder::default();
+
+        for pos in (0..width)
+            .flat_map(|x| (0..height).map(move |y| (x, y)))
+            .flat_map(|(x, y)| (0..width).map(move |z| (x, y, z)))
+        {
+            if !cells[pos.0][pos.1][pos.2] {
+                continue;
+            }
+            let cell = IVec3::new(pos.0 as i32, pos.1 as i32, pos.2 as i32);
+            // Voxel centers sit on integer coordinates, so cells start half a voxel lower.
+            let center = (cell.as_vec3() + 0.5) * scale as f32 - 0.5;
 
+            for face in FACES.iter().filter(|face| {
+                let neighbour = cell + face.normal;
+                neighbour.cmplt(IVec3::ZERO).any()
+                    || neighbour
+                        .cmpge(IVec3::new(width as i32, height as i32, width as i32))
+                        .any()
+                    || !cells[neighbour.x as usize][neighbour.y as usize][neighbour.z as usize]
+            }) {
+                let front = origin + (cell + face.normal) * scale as i32 + scale as i32 / 2;
+                let brightness = light::brightness(world.light(front));
+
+                mesh.push(
+                    face.corners
+                        .map(|corner| center + Vec3::from(corner) * scale as f32),
+                    face.normal.as_vec3(),
+                    face.uvs,
+                    [brightness; 4],
+                    false,
+                );
+            }
+        }
+
+        std::iter::once(mesh.build())
+            .chain((1..block::mesh_slots()).map(|_| MeshBuilder::default().build()))
+            .collect()
+    }
+
+    fn contains(pos: IVec3) -> bool {
+        pos.cmpge(IVec3::ZERO).all()
+            && pos
+                .cmplt(IVec3::new(
+                    CHUNK_WIDTH as i32,
+                    CHUNK_HEIGHT as i32,
+                    CHUNK_WIDTH as i32,
+                ))
+                .all()
+    }
+
+    fn voxel(&self, pos: IVec3) -> Option<VoxelID> {
+        self.data[pos.x as usize][pos.y as usize][pos.z as usize]
+    }
+
+    fn state(&self, pos: IVec3) -> BlockState {
+        self.states[pos.x as usize][pos.y as usize][pos.z as usize]
+    }
+
+    fn cells() -> impl Iterator<Item = IVec3> {
+        (0..CHUNK_WIDTH as i32)
+            .flat_map(|x| (0..CHUNK_HEIGHT as i32).map(move |y| (x, y)))
+            .flat_map(|(x, y)| (0..CHUNK_WIDTH as i32).map(move |z| IVec3::new(x, y, z)))
+    }
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    /// Adds a quad shaded per corner. It is split into triangles along the diagonal between
+    /// its first and third corners, or the other one if `flip` is set.
+    fn push(
+        &mut self,
+        corners: [Vec3; 4],
+        normal: Vec3,
+        uvs: [[f32; 2]; 4],
+        shades: [f32; 4],
+        flip: bool,
+    ) {
+        let base = self.positions.len() as u32;
+        self.positions
+            .extend(corners.iter().map(|corner| corner.to_array()));
+        self.normals.extend_from_slice(&[normal.to_array(); 4]);
+        self.uvs.extend_from_slice(&uvs);
+        self.colors
+            .extend(shades.iter().map(|&shade| [shade, shade, shade, 1.0]));
+        let quad = if flip {
+            [1, 2, 3, 3, 0, 1]
+        } else {
+            [0, 1, 2, 2, 3, 0]
+        };
+        self.indices.extend(quad.iter().map(|i| base + i));
+    }
+
+    fn build(self) -> Mesh {
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
         mesh
     }
 }
+
+struct Quad {
+    corners: [Vec3; 4],
+    uvs: [[f32; 2]; 4],
+    normal: Vec3,
+    /// Direction of the neighbour the quad is pressed against, if it lies on the boundary
+    /// of its voxel.
+    boundary: Option<IVec3>,
+}
+
+struct Face {
+    normal: IVec3,
+    /// Offsets of the corners from the center of the voxel, counter-clockwise.
+    corners: [[f32; 3]; 4],
+    uvs: [[f32; 2]; 4],
+}
+
+impl Face {
+    /// Texture coordinates of a point on the plane of the face, following its UV layout.
+    fn uv(&self, point: Vec3) -> [f32; 2] {
+        let origin = Vec3::from(self.corners[0]);
+        let s = (point - origin).dot(Vec3::from(self.corners[1]) - origin);
+        let t = (point - origin).dot(Vec3::from(self.corners[3]) - origin);
+        let [[u0, v0], [u1, v1], _, [u3, v3]] = self.uvs;
+        [
+            t.mul_add(u3 - u0, s.mul_add(u1 - u0, u0)),
+            t.mul_add(v3 - v0, s.mul_add(v1 - v0, v0)),
+        ]
+    }
+}
+
+const FACES: [Face; 6] = [
+    // Front
+    Face {
+        normal: IVec3::Z,
+        corners: [
+            [-0.5, -0.5, 0.5],
+            [0.5, -0.5, 0.5],
+            [0.5, 0.5, 0.5],
+            [-0.5, 0.5, 0.5],
+        ],
+        uvs: [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+    },
+    // Back
+    Face {
+        normal: IVec3::NEG_Z,
+        corners: [
+            [-0.5, 0.5, -0.5],
+            [0.5, 0.5, -0.5],
+            [0.5, -0.5, -0.5],
+            [-0.5, -0.5, -0.5],
+        ],
+        uvs: [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+    },
+    // Right
+    Face {
+        normal: IVec3::X,
+        corners: [
+            [0.5, -0.5, -0.5],
+            [0.5, 0.5, -0.5],
+            [0.5, 0.5, 0.5],
+            [0.5, -0.5, 0.5],
+        ],
+        uvs: [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+    },
+    // Left
+    Face {
+        normal: IVec3::NEG_X,
+        corners: [
+            [-0.5, -0.5, 0.5],
+            [-0.5, 0.5, 0.5],
+            [-0.5, 0.5, -0.5],
+            [-0.5, -0.5, -0.5],
+        ],
+        uvs: [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+    },
+    // Top
+    Face {
+        normal: IVec3::Y,
+        corners: [
+            [0.5, 0.5, -0.5],
+            [-0.5, 0.5, -0.5],
+            [-0.5, 0.5, 0.5],
+            [0.5, 0.5, 0.5],
+        ],
+        uvs: [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+    },
+    // Bottom
+    Face {
+        normal: IVec3::NEG_Y,
+        corners: [
+            [0.5, -0.5, 0.5],
+            [-0.5, -0.5, 0.5],
+            [-0.5, -0.5, -0.5],
+            [0.5, -0.5, -0.5],
+        ],
+        uvs: [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+    },
+];
+
+/// Corners of a quad, as offsets from the center of its voxel, and their UVs.
+type Corners = ([[f32; 3]; 4], [[f32; 2]; 4]);
+
+/// The two diagonal planes of [`Shape::Cross`], each seen from both sides.
+const CROSS: [Corners; 4] = [
+    (
+        [
+            [-0.5, -0.5, -0.5],
+            [0.5, -0.5, 0.5],
+            [0.5, 0.5, 0.5],
+            [-0.5, 0.5, -0.5],
+        ],
+        [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+    ),
+    (
+        [
+            [0.5, -0.5, 0.5],
+            [-0.5, -0.5, -0.5],
+            [-0.5, 0.5, -0.5],
+            [0.5, 0.5, 0.5],
+        ],
+        [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+    ),
+    (
+        [
+            [-0.5, -0.5, 0.5],
+            [0.5, -0.5, -0.5],
+            [0.5, 0.5, -0.5],
+            [-0.5, 0.5, 0.5],
+        ],
+        [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+    ),
+    (
+        [
+            [0.5, -0.5, -0.5],
+            [-0.5, -0.5, 0.5],
+            [-0.5, 0.5, 0.5],
+            [0.5, 0.5, -0.5],
+        ],
+        [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+    ),
+];
+
+/// Brightness of a vertex for each ambient occlusion level.
+const AO_CURVE: [f32; 4] = [0.4, 0.6, 0.8, 1.0];
+
+/// Ambient occlusion of a face corner, from 0 (fully occluded) to 3 (open), based on the
+/// three voxels in front of the face that touch the corner. `corner` is the offset of the
+/// vertex from the center of its voxel.
+fn vertex_ao(world: &VoxelWorld, front: IVec3, normal: IVec3, corner: Vec3) -> u8 {
+    let offset = corner.signum().as_ivec3();
+    let mut sides = [IVec3::ZERO; 2];
+    for (side, axis) in sides
+        .iter_mut()
+        .zip((0..3).filter(|&axis| normal[axis] == 0))
+    {
+        side[axis] = offset[axis];
+    }
+
+    let occludes = |offset: IVec3| !block::is_transparent(world.get(front + offset));
+    let (first, second) = (occludes(sides[0]), occludes(sides[1]));
+    if first && second {
+        0
+    } else {
+        3 - u8::from(first) - u8::from(second) - u8::from(occludes(sides[0] + sides[1]))
+    }
+}
+
+/// Whether the face of the block at `pos` pointing to `normal` continues into a visible face
+/// of the same block past its left, right, top and bottom edges, as seen in texture space.
+fn face_connections(
+    world: &VoxelWorld,
+    pos: IVec3,
+    voxel: VoxelID,
+    normal: IVec3,
+    quad: &Quad,
+) -> [bool; 4] {
+    let (u, v) = (
+        quad.corners[1] - quad.corners[0],
+        quad.corners[3] - quad.corners[0],
+    );
+    let (du, dv) = (
+        Vec2::from(quad.uvs[1]) - Vec2::from(quad.uvs[0]),
+        Vec2::from(quad.uvs[3]) - Vec2::from(quad.uvs[0]),
+    );
+
+    let mut connections = [false; 4];
+    for dir in [
+        IVec3::X,
+        IVec3::NEG_X,
+        IVec3::Y,
+        IVec3::NEG_Y,
+        IVec3::Z,
+        IVec3::NEG_Z,
+    ] {
+        if dir.dot(normal) != 0 {
+            continue;
+        }
+        let neighbour = pos + dir;
+        let connected = world.get(neighbour) == Some(voxel)
+            && world
+                .get(neighbour + normal)
+                .is_none_or(|front| !block::hides(voxel, front));
+        // The direction the edge lies in once mapped onto the texture.
+        let dir = dir.as_vec3();
+        let edge = dir.dot(u) * du + dir.dot(v) * dv;
+        let side = if edge.x.abs() > edge.y.abs() {
+            usize::from(edge.x > 0.0)
+        } else {
+            2 + usize::from(edge.y > 0.0)
+        };
+        connections[side] = connected;
+    }
+    connections
+}
+
+/// The direction a face of the unit cube points to once the model is transformed.
+fn rotate_normal(transform: Affine3A, normal: IVec3) -> IVec3 {
+    (transform.matrix3 * normal.as_vec3())
+        .normalize()
+        .round()
+        .as_ivec3()
+}
+
+/// Cheap deterministic hash of a voxel position, for picking per-voxel variations.
+#[must_use]
+pub const fn position_hash(pos: IVec3) -> u64 {
+    let hash = (pos.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (pos.y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (pos.z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    let hash = (hash ^ (hash >> 29)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash ^ (hash >> 32)
+}
+
+/// What produced a [`VoxelEditEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditKind {
+    Box,
+    Sphere,
+    Explosion,
+}
+
+/// Sent once for every batched edit of the world, listing the voxels it replaced.
+#[derive(Clone, Debug)]
+pub struct VoxelEditEvent {
+    pub kind: EditKind,
+    /// Bounds of the edited area, inclusive.
+    pub min: IVec3,
+    pub max: IVec3,
+    /// The voxels that changed, with what they hold now.
+    pub changes: Vec<(IVec3, Option<VoxelID>)>,
+}
+
+/// Every loaded chunk, keyed by its position in chunk coordinates.
+///
+/// Voxel positions are in world space, where the chunk at `(cx, cz)` covers
+/// `cx * CHUNK_WIDTH..(cx + 1) * CHUNK_WIDTH` on the X axis (and likewise on Z).
+#[derive(Default, Resource)]
+pub struct VoxelWorld {
+    pub chunks: HashMap<IVec2, Chunk>,
+    /// Chunks compressed by the [`super::cache::ChunkCache`]. They can still be read, and
+    /// are decompressed as soon as they are modified.
+    compressed: HashMap<IVec2, CompressedChunk>,
+    /// Block entities of decompressed chunks, left to be respawned.
+    restored_block_entities: Vec<(IVec3, BlockEntityData)>,
+    /// Where players spawn, found by [`super::spawn::find`] once the terrain around
+    /// [`super::spawn::WORLD_SPAWN`] is generated. Kept with the world so that it doesn't
+    /// move afterwards.
+    pub spawn: Option<IVec3>,
+    dirty: HashSet<IVec2>,
+    changed: Vec<IVec3>,
+    updates: Vec<IVec3>,
+    removed: Vec<(IVec3, VoxelID)>,
+    block_entities: HashMap<IVec3, Entity>,
+    block_entity_changes: Vec<IVec3>,
+    edits: Vec<VoxelEditEvent>,
+}
+
+impl VoxelWorld {
+    /// Splits a world position into its chunk position and the local position inside it.
+    #[must_use]
+    pub fn locate(pos: IVec3) -> Option<(IVec2, (usize, usize, usize))> {
+        let width = CHUNK_WIDTH as i32;
+        if !(0..CHUNK_HEIGHT as i32).contains(&pos.y) {
+            return None;
+        }
+        Some((
+            IVec2::new(pos.x.div_euclid(width), pos.z.div_euclid(width)),
+            (
+                pos.x.rem_euclid(width) as usize,
+                pos.y as usize,
+                pos.z.rem_euclid(width) as usize,
+            ),
+        ))
+    }
+
+    /// Whether the chunk at `chunk_pos` is loaded, compressed or not.
+    #[must_use]
+    pub fn has_chunk(&self, chunk_pos: IVec2) -> bool {
+        self.chunks.contains_key(&chunk_pos) || self.compressed.contains_key(&chunk_pos)
+    }
+
+    #[must_use]
+    pub fn is_loaded(&self, pos: IVec3) -> bool {
+        Self::locate(pos).is_some_and(|(chunk, _)| self.has_chunk(chunk))
+    }
+
+    /// The chunk at `chunk_pos`, or a decompressed copy of it if it is compressed.
+    #[must_use]
+    pub fn chunk(&self, chunk_pos: IVec2) -> Option<Cow<'_, Chunk>> {
+        self.chunks.get(&chunk_pos).map(Cow::Borrowed).or_else(|| {
+            self.compressed
+                .get(&chunk_pos)
+                .map(|chunk| Cow::Owned(chunk.decompress()))
+        })
+    }
+
+    /// Everything stored for the voxel at `pos`, if it is loaded.
+    #[must_use]
+    pub fn cell(&self, pos: IVec3) -> Option<Cell> {
+        let (chunk_pos, (x, y, z)) = Self::locate(pos)?;
+        match self.chunks.get(&chunk_pos) {
+            Some(chunk) => Some(chunk.cell(x, y, z)),
+            None => Some(self.compressed.get(&chunk_pos)?.cell(x, y, z)),
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, pos: IVec3) -> Option<VoxelID> {
+        self.cell(pos)?.voxel
+    }
+
+    #[must_use]
+    pub fn fluid_level(&self, pos: IVec3) -> u8 {
+        self.cell(pos).map_or(0, |cell| cell.fluid_level)
+    }
+
+    /// Packed light at `pos`, see [`Chunk::light`]. Everything above the world is in full sky light.
+    #[must_use]
+    pub fn light(&self, pos: IVec3) -> u8 {
+        if pos.y >= CHUNK_HEIGHT as i32 {
+            return light::MAX_LIGHT << 4;
+        }
+        self.cell(pos).map_or(0, |cell| cell.light)
+    }
+
+    pub fn set_light(&mut self, pos: IVec3, light: u8) {
+        let Some((chunk_pos, (x, y, z))) = Self::locate(pos) else {
+            return;
+        };
+        self.decompress(chunk_pos);
+        let Some(chunk) = self.chunks.get_mut(&chunk_pos) else {
+            return;
+        };
+        chunk.light[x][y][z] = light;
+        self.mark_dirty(pos);
+    }
+
+    #[must_use]
+    pub fn state(&self, pos: IVec3) -> BlockState {
+        self.cell(pos).map(|cell| cell.state).unwrap_or_default()
+    }
+
+    /// Changes the state of the voxel at `pos`, keeping the voxel itself.
+    pub fn set_state(&mut self, pos: IVec3, state: BlockState) {
+        let Some((chunk_pos, (x, y, z))) = Self::locate(pos) else {
+            return;
+        };
+        self.decompress(chunk_pos);
+        let Some(chunk) = self.chunks.get_mut(&chunk_pos) else {
+            return;
+        };
+        chunk.states[x][y][z] = state;
+        self.mark_dirty(pos);
+    }
+
+    /// Replaces the voxel at `pos`, clearing any fluid that was there.
+    pub fn set(&mut self, pos: IVec3, voxel: Option<VoxelID>) {
+        self.set_fluid(pos, voxel, 0);
+    }
+
+    /// Replaces the voxel at `pos` and sets its fluid level.
+    pub fn set_fluid(&mut self, pos: IVec3, voxel: Option<VoxelID>, level: u8) {
+        let Some((chunk_pos, (x, y, z))) = Self::locate(pos) else {
+            return;
+        };
+        self.decompress(chunk_pos);
+        let Some(chunk) = self.chunks.get_mut(&chunk_pos) else {
+            return;
+        };
+        if chunk.data[x][y][z] != voxel {
+            let has_block_entity = |voxel: Option<VoxelID>| {
+                voxel.is_some_and(|voxel| block::def(voxel).block_entity.is_some())
+            };
+            if has_block_entity(chunk.data[x][y][z]) || has_block_entity(voxel) {
+                self.block_entity_changes.push(pos);
+            }
+            if let Some(previous) = chunk.data[x][y][z] {
+                self.removed.push((pos, previous));
+            }
+            chunk.data[x][y][z] = voxel;
+            self.changed.push(pos);
+            self.updates.push(pos);
+        }
+        chunk.fluid_levels[x][y][z] = level;
+        chunk.states[x][y][z] = BlockState::default();
+        self.mark_dirty(pos);
+    }
+
+    /// Fills the box between `min` and `max`, inclusive, with `voxel`.
+    pub fn edit_box(&mut self, min: IVec3, max: IVec3, voxel: Option<VoxelID>) {
+        let (min, max) = (min.min(max), min.max(max));
+        let cells = (min.x..=max.x)
+            .flat_map(|x| (min.y..=max.y).map(move |y| (x, y)))
+            .flat_map(|(x, y)| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+            .collect::<Vec<_>>();
+        self.edit(EditKind::Box, min, max, &cells, voxel);
+    }
+
+    /// Fills every voxel whose center is within `radius` of `center` with `voxel`.
+    pub fn edit_sphere(&mut self, center: Vec3, radius: f32, voxel: Option<VoxelID>) {
+        let (min, max) = (
+            (center - radius).ceil().as_ivec3(),
+            (center + radius).floor().as_ivec3(),
+        );
+        let cells = (min.x..=max.x)
+            .flat_map(|x| (min.y..=max.y).map(move |y| (x, y)))
+            .flat_map(|(x, y)| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+            .filter(|pos| pos.as_vec3().distance_squared(center) <= radius * radius)
+            .collect::<Vec<_>>();
+        self.edit(EditKind::Sphere, min, max, &cells, voxel);
+    }
+
+    /// Blows up the voxels around `center`. Rays are cast outwards in every direction, and
+    /// lose strength with distance and with the blast resistance of what they go through.
+    /// Voxels are destroyed where a ray still has strength left. Fluids are never destroyed.
+    pub fn explode(&mut self, center: Vec3, power: f32) {
+        const GRID: i32 = 16;
+        const STEP: f32 = 0.3;
+        const FADE: f32 = STEP * 0.75;
+
+        let mut destroyed = HashSet::default();
+        let edge = |i: i32| i == 0 || i == GRID - 1;
+        for ray in (0..GRID)
+            .flat_map(|x| (0..GRID).map(move |y| (x, y)))
+            .flat_map(|(x, y)| (0..GRID).map(move |z| IVec3::new(x, y, z)))
+            .filter(|ray| edge(ray.x) || edge(ray.y) || edge(ray.z))
+        {
+            let dir = (ray.as_vec3() / (GRID - 1) as f32 * 2.0 - 1.0).normalize();
+            let mut strength = power * rand::random::<f32>().mul_add(0.6, 0.7);
+            // Strength fades by at least `FADE` every step, so the ray is spent after this
+            // many of them.
+            let steps = (strength / FADE).ceil() as usize;
+            for step in 0..steps {
+                let pos = (center + dir * (step as f32 * STEP)).round().as_ivec3();
+                if let Some(voxel) = self.get(pos) {
+                    strength = (block::def(voxel).blast_resistance + STEP).mul_add(-STEP, strength);
+                    if strength > 0.0 && fluid::Fluid::from_voxel(voxel).is_none() {
+                        destroyed.insert(pos);
+                    }
+                }
+                strength -= FADE;
+                if strength <= 0.0 {
+                    break;
+                }
+            }
+        }
+
+        let mut cells = destroyed.into_iter().collect::<Vec<_>>();
+        cells.sort_by_key(|pos| (pos.y, pos.x, pos.z));
+        let radius = IVec3::splat(power.ceil() as i32 * 2);
+        let center = center.round().as_ivec3();
+        self.edit(
+            EditKind::Explosion,
+            center - radius,
+            center + radius,
+            &cells,
+            None,
+        );
+    }
+
+    /// Replaces the voxels at `cells` in one batch. Chunks are only remeshed once, on the
+    /// next frame, and a single [`VoxelEditEvent`] is queued for the whole edit.
+    fn edit(
+        &mut self,
+        kind: EditKind,
+        min: IVec3,
+        max: IVec3,
+        cells: &[IVec3],
+        voxel: Option<VoxelID>,
+    ) {
+        let mut changes = Vec::new();
+        for &pos in cells {
+            if self.is_loaded(pos) && self.get(pos) != voxel {
+                self.set(pos, voxel);
+                changes.push((pos, voxel));
+            }
+        }
+        if !changes.is_empty() {
+            self.edits.push(VoxelEditEvent {
+                kind,
+                min,
+                max,
+                changes,
+            });
+        }
+    }
+
+    /// Returns the edits applied since the last call.
+    pub fn take_edits(&mut self) -> Vec<VoxelEditEvent> {
+        std::mem::take(&mut self.edits)
+    }
+
+    /// Walks the voxels along a ray, returning the first one that isn't air or fluid within
+    /// `max_distance`, and the normal of the face the ray entered it through. The normal is
+    /// zero if the ray starts inside that voxel.
+    #[must_use]
+    pub fn raycast(&self, origin: Vec3, dir: Vec3, max_distance: f32) -> Option<(IVec3, IVec3)> {
+        let dir = dir.normalize_or_zero();
+        if dir == Vec3::ZERO {
+            return None;
+        }
+        // Voxels are centered on integer positions, so their boundaries are half-way.
+        let start = origin + 0.5;
+        let mut cell = start.floor().as_ivec3();
+        let mut step = IVec3::ZERO;
+        let mut next = Vec3::splat(f32::INFINITY);
+        let mut delta = Vec3::splat(f32::INFINITY);
+        for axis in 0..3 {
+            if dir[axis] > 0.0 {
+                step[axis] = 1;
+                next[axis] = (cell[axis] as f32 + 1.0 - start[axis]) / dir[axis];
+            } else if dir[axis] < 0.0 {
+                step[axis] = -1;
+                next[axis] = (start[axis] - cell[axis] as f32) / -dir[axis];
+            }
+            if dir[axis] != 0.0 {
+                delta[axis] = 1.0 / dir[axis].abs();
+            }
+        }
+
+        // Every step crosses a boundary on one axis, and there are at most this many of them
+        // within `max_distance`.
+        let steps = ((dir.abs() * max_distance).floor() + 1.0).dot(Vec3::ONE) as usize;
+        let mut normal = IVec3::ZERO;
+        for _ in 0..=steps {
+            if let Some(voxel) = self.get(cell) {
+                if fluid::Fluid::from_voxel(voxel).is_none() {
+                    return Some((cell, normal));
+                }
+            }
+            let axis = if next.x < next.y && next.x < next.z {
+                0
+            } else if next.y < next.z {
+                1
+            } else {
+                2
+            };
+            if next[axis] > max_distance {
+                break;
+            }
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+            normal = IVec3::ZERO;
+            normal[axis] = -step[axis];
+        }
+        None
+    }
+
+    /// Queues a remesh of the chunk holding `pos`, and of the chunks bordering it if `pos`
+    /// is on their edge (or corner), since their faces sample the voxels next to them.
+    fn mark_dirty(&mut self, pos: IVec3) {
+        for dir in (-1..=1).flat_map(|x| (-1..=1).map(move |z| IVec3::new(x, 0, z))) {
+            let Some((chunk_pos, _)) = Self::locate(pos + dir) else {
+                continue;
+            };
+            if self.has_chunk(chunk_pos) {
+                self.dirty.insert(chunk_pos);
+            }
+        }
+    }
+
+    /// Compresses the chunk at `chunk_pos`, keeping the data of its block entities with it.
+    /// The block entities themselves must be despawned by the caller.
+    pub fn compress(&mut self, chunk_pos: IVec2, block_entities: Vec<(IVec3, BlockEntityData)>) {
+        if let Some(chunk) = self.chunks.remove(&chunk_pos) {
+            self.compressed
+                .insert(chunk_pos, CompressedChunk::compress(&chunk, block_entities));
+        }
+    }
+
+    /// Decompresses the chunk at `chunk_pos` if it is compressed. Its block entities are
+    /// respawned by [`super::block_entity::block_entity_system`].
+    pub fn decompress(&mut self, chunk_pos: IVec2) {
+        if let Some(compressed) = self.compressed.remove(&chunk_pos) {
+            self.chunks.insert(chunk_pos, compressed.decompress());
+            self.restored_block_entities
+                .extend(compressed.into_block_entities());
+        }
+    }
+
+    /// Every compressed chunk, with its position.
+    pub fn compressed_chunks(&self) -> impl Iterator<Item = (IVec2, &CompressedChunk)> {
+        self.compressed.iter().map(|(&pos, chunk)| (pos, chunk))
+    }
+
+    /// Returns the block entities of the chunks decompressed since the last call.
+    pub fn take_restored_block_entities(&mut self) -> Vec<(IVec3, BlockEntityData)> {
+        std::mem::take(&mut self.restored_block_entities)
+    }
+
+    /// Returns the positions whose voxel was replaced since the last call.
+    pub fn take_changed(&mut self) -> Vec<IVec3> {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Returns the positions whose voxel was replaced since the last call, for the block
+    /// updates of their neighbours. Unlike [`Self::take_changed`], this is drained once per
+    /// tick rather than once per frame.
+    pub fn take_updates(&mut self) -> Vec<IVec3> {
+        std::mem::take(&mut self.updates)
+    }
+
+    /// Returns the blocks removed or replaced since the last call, with the voxel they held.
+    /// Like [`Self::take_updates`], this is drained once per tick.
+    pub fn take_removed(&mut self) -> Vec<(IVec3, VoxelID)> {
+        std::mem::take(&mut self.removed)
+    }
+
+    /// Returns the chunks modified since the last call.
+    pub fn take_dirty(&mut self) -> HashSet<IVec2> {
+        std::mem::take(&mut self.dirty)
+    }
+
+    /// The block entity of the voxel at `pos`, once it has been spawned.
+    #[must_use]
+    pub fn block_entity(&self, pos: IVec3) -> Option<Entity> {
+        self.block_entities.get(&pos).copied()
+    }
+
+    /// Block entities of the chunk at `chunk_pos`, with their positions.
+    #[must_use]
+    pub fn chunk_block_entities(&self, chunk_pos: IVec2) -> Vec<(IVec3, Entity)> {
+        self.block_entities
+            .iter()
+            .filter(|&(&pos, _)| Self::locate(pos).map(|(chunk, _)| chunk) == Some(chunk_pos))
+            .map(|(&pos, &entity)| (pos, entity))
+            .collect()
+    }
+
+    pub fn insert_block_entity(&mut self, pos: IVec3, entity: Entity) {
+        self.block_entities.insert(pos, entity);
+    }
+
+    pub fn remove_block_entity(&mut self, pos: IVec3) -> Option<Entity> {
+        self.block_entities.remove(&pos)
+    }
+
+    /// Returns the positions where a block with a block entity was placed or replaced since
+    /// the last call.
+    pub fn take_block_entity_changes(&mut self) -> Vec<IVec3> {
+        std::mem::take(&mut self.block_entity_changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use super::*;
+
+    /// A world of a single chunk with a stone floor at the bottom, and stone at `blocks`.
+    fn floor(blocks: &[IVec3]) -> VoxelWorld {
+        let mut chunk = Chunk::new();
+        for (x, z) in (0..CHUNK_WIDTH).flat_map(|x| (0..CHUNK_WIDTH).map(move |z| (x, z))) {
+            chunk.data[x][0][z] = Some(block::STONE);
+        }
+        for pos in blocks {
+            chunk.data[pos.x as usize][pos.y as usize][pos.z as usize] = Some(block::STONE);
+        }
+        let mut world = VoxelWorld::default();
+        world.chunks.insert(IVec2::ZERO, chunk);
+        world
+    }
+
+    /// The shades and UVs of the corners of the top face of the block at `pos`, and whether
+    /// the face is split along the diagonal between its first and third corners.
+    fn top_face(world: &VoxelWorld, pos: IVec3) -> ([f32; 4], [[f32; 2]; 4], bool) {
+        let voxel = world.get(pos).expect("the block is there");
+        let mesh =
+            &world.chunks[&IVec2::ZERO].create_mesh(world, IVec2::ZERO)[block::mesh_slot(voxel)];
+        let (
+            Some(VertexAttributeValues::Float32x3(positions)),
+            Some(VertexAttributeValues::Float32x4(colors)),
+            Some(VertexAttributeValues::Float32x2(uvs)),
+            Some(Indices::U32(indices)),
+        ) = (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+            mesh.attribute(Mesh::ATTRIBUTE_COLOR),
+            mesh.attribute(Mesh::ATTRIBUTE_UV_0),
+            mesh.indices(),
+        )
+        else {
+            panic!("chunk meshes have positions, colors, UVs and indices");
+        };
+
+        let center = pos.as_vec3() + Vec3::Y * 0.5;
+        let base = (0..positions.len())
+            .step_by(4)
+            .find(|&base| {
+                positions[base..base + 4].iter().all(|&corner| {
+                    (Vec3::from(corner) - center)
+                        .abs()
+                        .cmple(Vec3::new(0.5, 0.0, 0.5))
+                        .all()
+                })
+            })
+            .expect("the top face is visible");
+        let triangles = &indices[base / 4 * 6..][..6];
+        (
+            [0, 1, 2, 3].map(|i| colors[base + i][0]),
+            [0, 1, 2, 3].map(|i| uvs[base + i]),
+            triangles.iter().filter(|&&i| i == base as u32).count() == 2,
+        )
+    }
+
+    #[test]
+    fn corners_are_occluded_by_the_blocks_around_them() {
+        // The corner of the top face of the floor towards +X and +Z.
+        let front = IVec3::new(8, 1, 8);
+        let corner = Vec3::splat(0.5);
+        let ao = |blocks: &[IVec3]| vertex_ao(&floor(blocks), front, IVec3::Y, corner);
+        let (side_x, side_z) = (front + IVec3::X, front + IVec3::Z);
+        let diagonal = front + IVec3::new(1, 0, 1);
+
+        assert_eq!(ao(&[]), 3);
+        assert_eq!(ao(&[diagonal]), 2);
+        assert_eq!(ao(&[side_z]), 2);
+        assert_eq!(ao(&[side_x, diagonal]), 1);
+        // Two sides close the corner off, whatever is between them.
+        assert_eq!(ao(&[side_x, side_z]), 0);
+        // Blocks around the other corners don't matter.
+        assert_eq!(
+            ao(&[front + IVec3::NEG_X, front + IVec3::new(-1, 0, -1)]),
+            3
+        );
+    }
+
+    #[test]
+    fn quads_are_split_along_their_darker_diagonal() {
+        let pos = IVec3::new(8, 0, 8);
+        let mut splits = HashSet::new();
+        for dir in [
+            IVec3::new(1, 0, 1),
+            IVec3::new(-1, 0, 1),
+            IVec3::new(1, 0, -1),
+            IVec3::new(-1, 0, -1),
+        ] {
+            let (shades, _, first_diagonal) = top_face(&floor(&[pos + IVec3::Y + dir]), pos);
+            let darkest = (0..4)
+                .min_by(|&a, &b| shades[a].total_cmp(&shades[b]))
+                .unwrap();
+            assert_eq!(
+                shades
+                    .iter()
+                    .filter(|&&shade| shade > shades[darkest])
+                    .count(),
+                3
+            );
+            assert_eq!(first_diagonal, darkest % 2 == 0);
+            splits.insert(first_diagonal);
+        }
+        // Both ways of splitting a quad were checked.
+        assert_eq!(splits.len(), 2);
+    }
+
+    #[test]
+    fn glass_connects_to_the_glass_next_to_it() {
+        let pane = IVec3::new(8, 1, 8);
+        let tiles = |neighbours: &[IVec3]| {
+            let mut world = floor(&[]);
+            let chunk = world.chunks.get_mut(&IVec2::ZERO).unwrap();
+            for pos in std::iter::once(&pane).chain(neighbours) {
+                chunk.data[pos.x as usize][pos.y as usize][pos.z as usize] = Some(block::GLASS);
+            }
+            top_face(&world, pane).1
+        };
+
+        // A lone pane is framed on every side, with the first tile.
+        let alone = tiles(&[]);
+        assert!(alone
+            .iter()
+            .all(|&[u, v]| (0.0..=0.25).contains(&u) && (0.0..=0.25).contains(&v)));
+        // Each neighbour opens a different side of the frame.
+        let sides = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z]
+            .map(|dir| tiles(&[pane + dir]))
+            .to_vec();
+        for (i, side) in sides.iter().enumerate() {
+            assert_ne!(*side, alone);
+            assert!(sides[..i].iter().all(|other| other != side));
+        }
+        // Glass above or below doesn't show on the top face.
+        assert_eq!(tiles(&[pane + IVec3::NEG_Y]), alone);
+    }
+
+    #[test]
+    fn batched_edits_are_reported_once() {
+        let mut world = VoxelWorld::default();
+        for x in -1..=2 {
+            world.chunks.insert(IVec2::new(x, 0), Chunk::new());
+        }
+
+        // A box across the border between the first two chunks, touching neither of the others.
+        world.edit_box(
+            IVec3::new(12, 1, 4),
+            IVec3::new(19, 3, 6),
+            Some(block::STONE),
+        );
+        let edits = world.take_edits();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].kind, EditKind::Box);
+        assert_eq!(edits[0].changes.len(), 8 * 3 * 3);
+        assert_eq!(
+            world.take_dirty(),
+            [IVec2::ZERO, IVec2::X].into_iter().collect::<HashSet<_>>()
+        );
+
+        // Voxels that already hold the block are left out, and so is an edit changing none.
+        world.edit_sphere(Vec3::new(16.0, 2.0, 5.0), 1.0, Some(block::STONE));
+        assert!(world.take_edits().is_empty());
+        assert!(world.take_dirty().is_empty());
+    }
+}